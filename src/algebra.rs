@@ -1,5 +1,8 @@
 use crate::{Decode, DecodeError};
-use std::io::Read;
+use std::{
+    io::Read,
+    ops::{Add, AddAssign, Mul, Neg, Sub},
+};
 
 #[derive(Clone, Copy, Debug, Decode, Default, PartialEq, PartialOrd)]
 pub struct Vector3 {
@@ -12,6 +15,75 @@ impl Vector3 {
     pub fn new(x: f32, y: f32, z: f32) -> Self {
         Self { x, y, z }
     }
+
+    pub fn dot(&self, other: &Vector3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Vector3) -> Vector3 {
+        Vector3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Returns the vector scaled to unit length, or the vector itself if its length is zero.
+    pub fn normalized(&self) -> Vector3 {
+        let length = self.length();
+
+        if length > 0.0 {
+            *self * (1.0 / length)
+        } else {
+            *self
+        }
+    }
+
+    pub fn lerp(&self, other: &Vector3, t: f32) -> Vector3 {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Add for Vector3 {
+    type Output = Vector3;
+
+    fn add(self, other: Vector3) -> Vector3 {
+        Vector3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl AddAssign for Vector3 {
+    fn add_assign(&mut self, other: Vector3) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vector3 {
+    type Output = Vector3;
+
+    fn sub(self, other: Vector3) -> Vector3 {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, scalar: f32) -> Vector3 {
+        Vector3::new(self.x * scalar, self.y * scalar, self.z * scalar)
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+
+    fn neg(self) -> Vector3 {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Decode, Default, PartialEq, PartialOrd)]
@@ -28,6 +100,12 @@ impl Vector4 {
     }
 }
 
+impl From<Vector4> for Vector3 {
+    fn from(vector: Vector4) -> Self {
+        Self::new(vector.x, vector.y, vector.z)
+    }
+}
+
 impl From<Vector3> for Vector4 {
     fn from(vector: Vector3) -> Self {
         Self {
@@ -64,6 +142,19 @@ impl<T: Decode<Output = T>> QuantizedQuaternion<T> {
     }
 }
 
+impl<T: Decode<Output = T> + Copy + Into<f64>> QuantizedQuaternion<T> {
+    /// Converts the quantized components into a unit quaternion.
+    pub fn dequantize(&self) -> Quaternion {
+        Quaternion::new(
+            self.x.into() as f32,
+            self.y.into() as f32,
+            self.z.into() as f32,
+            self.w.into() as f32,
+        )
+        .normalized()
+    }
+}
+
 impl<T: Decode<Output = T>> Decode for QuantizedQuaternion<T> {
     fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
         Ok(Self {
@@ -75,6 +166,123 @@ impl<T: Decode<Output = T>> Decode for QuantizedQuaternion<T> {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    /// Returns the quaternion scaled to unit length, or the identity if its length is zero.
+    pub fn normalized(&self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+
+        if length > 0.0 {
+            Quaternion::new(
+                self.x / length,
+                self.y / length,
+                self.z / length,
+                self.w / length,
+            )
+        } else {
+            Quaternion::identity()
+        }
+    }
+
+    /// Interpolates along the shortest arc between two rotations.
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Quaternion {
+        let mut cosine = self.dot(other);
+        let mut other = *other;
+
+        if cosine < 0.0 {
+            cosine = -cosine;
+            other = Quaternion::new(-other.x, -other.y, -other.z, -other.w);
+        }
+
+        let (a, b) = if cosine > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = cosine.acos();
+            let sine = angle.sin();
+
+            (((1.0 - t) * angle).sin() / sine, (t * angle).sin() / sine)
+        };
+
+        Quaternion::new(
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+            self.w * a + other.w * b,
+        )
+        .normalized()
+    }
+
+    /// Extracts the rotation of an orthonormal basis given as the rows of a row-vector matrix.
+    pub fn from_basis(right: &Vector3, up: &Vector3, at: &Vector3) -> Quaternion {
+        let trace = right.x + up.y + at.z;
+
+        let quaternion = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+
+            Quaternion::new(
+                (up.z - at.y) / s,
+                (at.x - right.z) / s,
+                (right.y - up.x) / s,
+                0.25 * s,
+            )
+        } else if right.x > up.y && right.x > at.z {
+            let s = (1.0 + right.x - up.y - at.z).sqrt() * 2.0;
+
+            Quaternion::new(
+                0.25 * s,
+                (up.x + right.y) / s,
+                (at.x + right.z) / s,
+                (up.z - at.y) / s,
+            )
+        } else if up.y > at.z {
+            let s = (1.0 + up.y - right.x - at.z).sqrt() * 2.0;
+
+            Quaternion::new(
+                (up.x + right.y) / s,
+                0.25 * s,
+                (at.y + up.z) / s,
+                (at.x - right.z) / s,
+            )
+        } else {
+            let s = (1.0 + at.z - right.x - up.y).sqrt() * 2.0;
+
+            Quaternion::new(
+                (at.x + right.z) / s,
+                (at.y + up.z) / s,
+                0.25 * s,
+                (right.y - up.x) / s,
+            )
+        };
+
+        quaternion.normalized()
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
 pub struct Matrix {
     pub right: Vector4,
@@ -84,6 +292,129 @@ pub struct Matrix {
     pub flags: u64,
 }
 
+impl Matrix {
+    pub fn identity() -> Self {
+        Self {
+            right: Vector4::new(1.0, 0.0, 0.0, 0.0),
+            up: Vector4::new(0.0, 1.0, 0.0, 0.0),
+            at: Vector4::new(0.0, 0.0, 1.0, 0.0),
+            position: Vector4::new(0.0, 0.0, 0.0, 1.0),
+            flags: 0,
+        }
+    }
+
+    /// Builds a matrix that scales, then rotates, then translates.
+    pub fn from_scale_rotation_translation(
+        scale: Vector3,
+        rotation: Quaternion,
+        translation: Vector3,
+    ) -> Self {
+        let Quaternion { x, y, z, w } = rotation.normalized();
+
+        let right = Vector3::new(
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y + z * w),
+            2.0 * (x * z - y * w),
+        ) * scale.x;
+        let up = Vector3::new(
+            2.0 * (x * y - z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z + x * w),
+        ) * scale.y;
+        let at = Vector3::new(
+            2.0 * (x * z + y * w),
+            2.0 * (y * z - x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ) * scale.z;
+
+        Self {
+            right: right.into(),
+            up: up.into(),
+            at: at.into(),
+            position: (translation, 1.0).into(),
+            flags: 0,
+        }
+    }
+
+    /// Splits the matrix into scale, rotation and translation, assuming it has no shear.
+    pub fn decompose(&self) -> (Vector3, Quaternion, Vector3) {
+        let right = Vector3::from(self.right);
+        let up = Vector3::from(self.up);
+        let at = Vector3::from(self.at);
+        let mut scale = Vector3::new(right.length(), up.length(), at.length());
+
+        if right.cross(&up).dot(&at) < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        let rotation = Quaternion::from_basis(
+            &(right * (1.0 / scale.x)),
+            &(up * (1.0 / scale.y)),
+            &(at * (1.0 / scale.z)),
+        );
+
+        (scale, rotation, self.position.into())
+    }
+
+    pub fn transform_point(&self, point: &Vector3) -> Vector3 {
+        self.transform_vector(point) + self.position.into()
+    }
+
+    pub fn transform_vector(&self, vector: &Vector3) -> Vector3 {
+        Vector3::from(self.right) * vector.x
+            + Vector3::from(self.up) * vector.y
+            + Vector3::from(self.at) * vector.z
+    }
+
+    /// Returns the inverse of an affine matrix, or `None` if it is singular.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let right = Vector3::from(self.right);
+        let up = Vector3::from(self.up);
+        let at = Vector3::from(self.at);
+        let determinant = right.cross(&up).dot(&at);
+
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let reciprocal = 1.0 / determinant;
+        let columns = [
+            up.cross(&at) * reciprocal,
+            at.cross(&right) * reciprocal,
+            right.cross(&up) * reciprocal,
+        ];
+        let inverse_right = Vector3::new(columns[0].x, columns[1].x, columns[2].x);
+        let inverse_up = Vector3::new(columns[0].y, columns[1].y, columns[2].y);
+        let inverse_at = Vector3::new(columns[0].z, columns[1].z, columns[2].z);
+        let position = Vector3::from(self.position);
+        let inverse_position =
+            -(inverse_right * position.x + inverse_up * position.y + inverse_at * position.z);
+
+        Some(Self {
+            right: inverse_right.into(),
+            up: inverse_up.into(),
+            at: inverse_at.into(),
+            position: (inverse_position, 1.0).into(),
+            flags: self.flags,
+        })
+    }
+}
+
+/// Concatenates row-vector transforms, so `a * b` applies `a` first.
+impl Mul for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, other: &Matrix) -> Matrix {
+        Matrix {
+            right: other.transform_vector(&self.right.into()).into(),
+            up: other.transform_vector(&self.up.into()).into(),
+            at: other.transform_vector(&self.at.into()).into(),
+            position: (other.transform_point(&self.position.into()), 1.0).into(),
+            flags: self.flags,
+        }
+    }
+}
+
 impl Decode for Matrix {
     fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
         let right = Vector3::decode(reader, ())?.into();
@@ -110,6 +441,34 @@ pub struct Plane {
     pub d: f32,
 }
 
+impl Plane {
+    pub fn normal(&self) -> Vector3 {
+        Vector3::new(self.a, self.b, self.c)
+    }
+
+    /// The signed distance of `point` to the plane, `ax + by + cz + d`, positive in front of it.
+    /// It is only a true distance if the normal has unit length.
+    pub fn distance(&self, point: &Vector3) -> f32 {
+        self.normal().dot(point) + self.d
+    }
+
+    /// Scales the plane so that its normal has unit length.
+    pub fn normalized(&self) -> Plane {
+        let length = self.normal().length();
+
+        if length > 0.0 {
+            Plane {
+                a: self.a / length,
+                b: self.b / length,
+                c: self.c / length,
+                d: self.d / length,
+            }
+        } else {
+            self.clone()
+        }
+    }
+}
+
 #[derive(Clone, Debug, Decode, Default, PartialEq, PartialOrd)]
 pub struct QuantizedPlane {
     pub a: u8,
@@ -126,3 +485,44 @@ pub struct Rectangle {
     pub width: i32,
     pub height: i32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn decompose_round_trip() {
+        let rotation = Quaternion::new(0.2, -0.4, 0.1, 0.9).normalized();
+        let matrix = Matrix::from_scale_rotation_translation(
+            Vector3::new(2.0, 2.0, 2.0),
+            rotation,
+            Vector3::new(1.0, -3.0, 5.0),
+        );
+        let (scale, decomposed, translation) = matrix.decompose();
+
+        assert_close(scale, Vector3::new(2.0, 2.0, 2.0));
+        assert_close(translation, Vector3::new(1.0, -3.0, 5.0));
+        assert!(decomposed.dot(&rotation).abs() > 0.9999);
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let matrix = Matrix::from_scale_rotation_translation(
+            Vector3::new(1.0, 3.0, 0.5),
+            Quaternion::new(0.5, 0.5, -0.5, 0.5),
+            Vector3::new(4.0, 0.0, -2.0),
+        );
+        let point = Vector3::new(0.3, 7.0, -1.5);
+        let inverse = matrix.inverse().unwrap();
+
+        assert_close(
+            inverse.transform_point(&matrix.transform_point(&point)),
+            point,
+        );
+        assert_close((&matrix * &inverse).transform_point(&point), point);
+    }
+}
@@ -0,0 +1,553 @@
+use crate::{
+    hash, AnimationKey, AnimationKeys, Clump, Frame, Interpolation, ModelPart, Quaternion, Uv,
+    UvKey, Vector3, VisibilityState, UV_KEY_SET_COUNT,
+};
+use std::collections::HashMap;
+
+/// What happens when a track is sampled outside of its keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Holds the first or last key.
+    Clamp,
+    /// Repeats the keys, wrapping the last key time back to the first.
+    Loop,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UvTrackError {
+    NotAUvTrack,
+    /// A set of some key animates a different number of vertices than the part has. Animated
+    /// coordinates are not stored with vertex indices, so only whole sets can be bound.
+    VertexCountMismatch {
+        set: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SampledValue {
+    Rotation(Quaternion),
+    Translation(Vector3),
+    /// The coordinates of the animated vertices of each UV set.
+    Uvs(UvKey),
+    Visibility(VisibilityState),
+}
+
+impl AnimationKey {
+    /// The time of every key, either stored explicitly or spaced by `time_step`.
+    pub fn key_times(&self) -> Vec<f32> {
+        match &self.times {
+            Some(times) => times.clone(),
+            None => (0..self.key_count.max(0))
+                .map(|index| index as f32 * self.time_step)
+                .collect(),
+        }
+    }
+
+    /// The time of the last key.
+    pub fn duration(&self) -> f32 {
+        self.key_times().last().copied().unwrap_or_default()
+    }
+
+    /// Evaluates the track at `time`.
+    ///
+    /// Cubic spline tracks use Catmull-Rom tangents, the same ones the glTF exporter writes.
+    /// Visibility changes at the key time. Returns `None` for empty tracks and for `Shape` tracks,
+    /// which need the mesh they deform.
+    pub fn sample(&self, time: f32, wrap_mode: WrapMode) -> Option<SampledValue> {
+        let times = self.key_times();
+        let (index, t) = locate(&times, time, wrap_mode)?;
+        let next = (index + 1).min(times.len() - 1);
+
+        match &self.keys {
+            AnimationKeys::Rotations(rotations) => {
+                let rotations =
+                    aligned_rotations(rotations.iter().map(|rotation| rotation.dequantize()));
+
+                if rotations.len() < times.len() {
+                    return None;
+                }
+
+                let rotation = match self.interpolation_type {
+                    Interpolation::Linear => rotations[index].slerp(&rotations[next], t),
+                    Interpolation::CubicSpline => {
+                        let values = rotations
+                            .iter()
+                            .flat_map(|rotation| [rotation.x, rotation.y, rotation.z, rotation.w])
+                            .collect::<Vec<_>>();
+                        let value = hermite(&times, &values, 4, index, t);
+
+                        Quaternion::new(value[0], value[1], value[2], value[3]).normalized()
+                    }
+                };
+
+                Some(SampledValue::Rotation(rotation))
+            }
+            AnimationKeys::Translations(translations) => {
+                let values = translations
+                    .iter()
+                    .flat_map(|translation| [translation.x, translation.y, translation.z])
+                    .collect::<Vec<_>>();
+                let value = self.interpolate(&times, &values, 3, index, t)?;
+
+                Some(SampledValue::Translation(Vector3::new(
+                    value[0], value[1], value[2],
+                )))
+            }
+            AnimationKeys::Uvs(uv_keys) => {
+                if uv_keys.len() < times.len() {
+                    return None;
+                }
+
+                let mut uv_sets: [Vec<Uv>; UV_KEY_SET_COUNT] = Default::default();
+
+                for (set, sampled) in uv_sets.iter_mut().enumerate() {
+                    let count = uv_keys
+                        .iter()
+                        .map(|uv_key| uv_key.uv_sets[set].len())
+                        .min()
+                        .unwrap_or_default();
+
+                    if count == 0 {
+                        continue;
+                    }
+
+                    let values = uv_keys
+                        .iter()
+                        .flat_map(|uv_key| &uv_key.uv_sets[set][..count])
+                        .flat_map(|uv| [uv.u, uv.v])
+                        .collect::<Vec<_>>();
+
+                    *sampled = self
+                        .interpolate(&times, &values, count * 2, index, t)?
+                        .chunks_exact(2)
+                        .map(|uv| Uv::new(uv[0], uv[1]))
+                        .collect();
+                }
+
+                Some(SampledValue::Uvs(UvKey { uv_sets }))
+            }
+            AnimationKeys::VisibilityStates(visibility_states) => {
+                let key = if t >= 1.0 { next } else { index };
+
+                visibility_states
+                    .get(key)
+                    .or_else(|| visibility_states.last())
+                    .copied()
+                    .map(SampledValue::Visibility)
+            }
+            AnimationKeys::Shapes(_) => None,
+        }
+    }
+
+    /// Whether this is a UV track of the model part at `material_block_index` in the mesh named
+    /// by `target_hash`.
+    pub fn is_uv_track_for(&self, target_hash: u32, material_block_index: usize) -> bool {
+        matches!(self.keys, AnimationKeys::Uvs(_))
+            && self.target_hash == target_hash
+            && self.material_block_index as usize == material_block_index
+    }
+
+    /// Evaluates a UV track of `model_part`, as selected by `is_uv_track_for`, at `time` and
+    /// returns the coordinates of every vertex, laid out like `Vertex::uvs`.
+    ///
+    /// A set animates every vertex of the part in vertex order, or none of them if it is empty
+    /// in every key. Sets of any other length are rejected rather than guessed at.
+    pub fn animate_uvs(
+        &self,
+        model_part: &ModelPart,
+        time: f32,
+        wrap_mode: WrapMode,
+    ) -> Result<Vec<Vec<(f32, f32)>>, UvTrackError> {
+        let uv_keys = match &self.keys {
+            AnimationKeys::Uvs(uv_keys) => uv_keys,
+            _ => return Err(UvTrackError::NotAUvTrack),
+        };
+        let expected = model_part.vertices.len();
+
+        for set in 0..UV_KEY_SET_COUNT {
+            let animated = uv_keys.iter().any(|uv_key| !uv_key.uv_sets[set].is_empty());
+
+            if let Some(uv_key) = uv_keys
+                .iter()
+                .find(|uv_key| animated && uv_key.uv_sets[set].len() != expected)
+            {
+                return Err(UvTrackError::VertexCountMismatch {
+                    set,
+                    expected,
+                    actual: uv_key.uv_sets[set].len(),
+                });
+            }
+        }
+
+        let uv_sets: [Vec<Uv>; UV_KEY_SET_COUNT] = match self.sample(time, wrap_mode) {
+            Some(SampledValue::Uvs(uv_key)) => uv_key.uv_sets,
+            _ => Default::default(),
+        };
+
+        let uvs = model_part
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| {
+                let mut uvs = vertex.uvs.clone();
+
+                for (uv, animated) in uvs.iter_mut().zip(&uv_sets) {
+                    if let Some(animated) = animated.get(index) {
+                        *uv = (animated.u, animated.v);
+                    }
+                }
+
+                uvs
+            })
+            .collect();
+
+        Ok(uvs)
+    }
+
+    fn interpolate(
+        &self,
+        times: &[f32],
+        values: &[f32],
+        components: usize,
+        index: usize,
+        t: f32,
+    ) -> Option<Vec<f32>> {
+        if components == 0 || values.len() < times.len() * components {
+            return None;
+        }
+
+        let next = (index + 1).min(times.len() - 1);
+        let value = match self.interpolation_type {
+            Interpolation::Linear => (0..components)
+                .map(|component| {
+                    let a = values[index * components + component];
+                    let b = values[next * components + component];
+
+                    a + (b - a) * t
+                })
+                .collect(),
+            Interpolation::CubicSpline => hermite(times, values, components, index, t),
+        };
+
+        Some(value)
+    }
+}
+
+/// Maps `target_hash` to the frames animation tracks drive.
+///
+/// Tracks name their target by bone id, frame id or the hash of the frame name.
+#[derive(Clone, Debug, Default)]
+pub struct TrackTargets {
+    targets: HashMap<u32, usize>,
+}
+
+impl TrackTargets {
+    /// Indexes `frames`, adding the bone ids of `clump` for frames that are bones.
+    pub fn new(frames: &[&Frame], clump: Option<&Clump>) -> Self {
+        let mut targets = Self::default();
+
+        for (index, frame) in frames.iter().enumerate() {
+            targets.insert(frame.id, index);
+            targets.insert(hash(frame.name.as_bytes()), index);
+        }
+
+        if let Some(clump) = clump {
+            for (bone_index, bone) in clump.bone_count.iter().enumerate() {
+                if let Some(index) = frames
+                    .iter()
+                    .position(|frame| frame.bone_index == bone_index as i32)
+                {
+                    targets.insert(bone.bone_id, index);
+                }
+            }
+        }
+
+        targets
+    }
+
+    pub fn insert(&mut self, target_hash: u32, index: usize) {
+        self.targets.insert(target_hash, index);
+    }
+
+    pub fn get(&self, target_hash: u32) -> Option<usize> {
+        self.targets.get(&target_hash).copied()
+    }
+
+    /// Pairs every rotation, translation and visibility track with the frame it drives. Tracks
+    /// whose target is unknown are left out.
+    pub fn bind<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a AnimationKey>,
+    ) -> Vec<(usize, &'a AnimationKey)> {
+        keys.into_iter()
+            .filter(|key| {
+                matches!(
+                    key.keys,
+                    AnimationKeys::Rotations(_)
+                        | AnimationKeys::Translations(_)
+                        | AnimationKeys::VisibilityStates(_)
+                )
+            })
+            .filter_map(|key| Some((self.get(key.target_hash)?, key)))
+            .collect()
+    }
+}
+
+/// Returns the key preceding `time` and how far `time` is towards the following key.
+pub(crate) fn locate(times: &[f32], time: f32, wrap_mode: WrapMode) -> Option<(usize, f32)> {
+    let first = *times.first()?;
+    let last = *times.last()?;
+
+    let time = match wrap_mode {
+        WrapMode::Loop if last > first => first + (time - first).rem_euclid(last - first),
+        _ => time.clamp(first, last),
+    };
+
+    let index = times[..times.len() - 1]
+        .iter()
+        .rposition(|key_time| *key_time <= time)
+        .unwrap_or(0);
+    let next = (index + 1).min(times.len() - 1);
+    let duration = times[next] - times[index];
+    let t = if duration > 0.0 {
+        ((time - times[index]) / duration).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    Some((index, t))
+}
+
+/// Flips rotations onto the hemisphere of their predecessor so interpolation takes the short way.
+pub(crate) fn aligned_rotations(rotations: impl Iterator<Item = Quaternion>) -> Vec<Quaternion> {
+    let mut aligned: Vec<Quaternion> = Vec::new();
+
+    for mut rotation in rotations {
+        if let Some(previous) = aligned.last() {
+            if rotation.dot(previous) < 0.0 {
+                rotation = Quaternion::new(-rotation.x, -rotation.y, -rotation.z, -rotation.w);
+            }
+        }
+
+        aligned.push(rotation);
+    }
+
+    aligned
+}
+
+/// Catmull-Rom tangent of key `index`, per unit of time.
+pub(crate) fn catmull_rom_tangent(
+    times: &[f32],
+    values: &[f32],
+    components: usize,
+    index: usize,
+) -> Vec<f32> {
+    let previous = index.saturating_sub(1);
+    let next = (index + 1).min(times.len() - 1);
+    let duration = times[next] - times[previous];
+
+    (0..components)
+        .map(|component| {
+            if duration > 0.0 {
+                (values[next * components + component] - values[previous * components + component])
+                    / duration
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn hermite(
+    times: &[f32],
+    values: &[f32],
+    components: usize,
+    index: usize,
+    t: f32,
+) -> Vec<f32> {
+    let next = (index + 1).min(times.len() - 1);
+    let duration = times[next] - times[index];
+    let start_tangent = catmull_rom_tangent(times, values, components, index);
+    let end_tangent = catmull_rom_tangent(times, values, components, next);
+
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+    let h10 = t3 - 2.0 * t2 + t;
+    let h01 = -2.0 * t3 + 3.0 * t2;
+    let h11 = t3 - t2;
+
+    (0..components)
+        .map(|component| {
+            h00 * values[index * components + component]
+                + h10 * duration * start_tangent[component]
+                + h01 * values[next * components + component]
+                + h11 * duration * end_tangent[component]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vertex;
+
+    fn translation_key(interpolation_type: Interpolation) -> AnimationKey {
+        AnimationKey {
+            type_: 1,
+            target_hash: 0,
+            time_step: 1.0,
+            key_count: 3,
+            material_block_index: 0,
+            bounding_box_maximum: None,
+            interpolation_type,
+            times: None,
+            keys: AnimationKeys::Translations(vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(4.0, 0.0, 0.0),
+            ]),
+            adaptive_differential_pulse_code_modulation: None,
+        }
+    }
+
+    #[test]
+    fn samples_linear_track_with_wrapping() {
+        let key = translation_key(Interpolation::Linear);
+
+        assert_eq!(
+            key.sample(0.5, WrapMode::Clamp),
+            Some(SampledValue::Translation(Vector3::new(1.0, 0.0, 0.0)))
+        );
+        assert_eq!(
+            key.sample(5.0, WrapMode::Clamp),
+            Some(SampledValue::Translation(Vector3::new(4.0, 0.0, 0.0)))
+        );
+        assert_eq!(
+            key.sample(2.5, WrapMode::Loop),
+            Some(SampledValue::Translation(Vector3::new(1.0, 0.0, 0.0)))
+        );
+    }
+
+    fn uv_key(coordinates: &[f32]) -> AnimationKey {
+        let uv_key = |offset| UvKey {
+            uv_sets: [
+                coordinates
+                    .iter()
+                    .map(|u| Uv::new(u + offset, 0.0))
+                    .collect(),
+                Vec::new(),
+            ],
+        };
+
+        AnimationKey {
+            type_: 3,
+            key_count: 2,
+            keys: AnimationKeys::Uvs(vec![uv_key(0.0), uv_key(1.0)]),
+            ..translation_key(Interpolation::Linear)
+        }
+    }
+
+    fn uv_part() -> ModelPart {
+        let vertex = Vertex {
+            uvs: vec![(0.5, 0.5), (0.25, 0.25)],
+            ..Default::default()
+        };
+
+        ModelPart {
+            vertices: vec![vertex.clone(), vertex],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn animates_part_uvs() {
+        assert_eq!(
+            uv_key(&[0.0, 2.0]).animate_uvs(&uv_part(), 0.5, WrapMode::Clamp),
+            Ok(vec![
+                vec![(0.5, 0.0), (0.25, 0.25)],
+                vec![(2.5, 0.0), (0.25, 0.25)],
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_partial_uv_sets() {
+        assert_eq!(
+            uv_key(&[0.0]).animate_uvs(&uv_part(), 0.5, WrapMode::Clamp),
+            Err(UvTrackError::VertexCountMismatch {
+                set: 0,
+                expected: 2,
+                actual: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn cubic_spline_passes_through_keys() {
+        let key = translation_key(Interpolation::CubicSpline);
+
+        assert_eq!(
+            key.sample(1.0, WrapMode::Clamp),
+            Some(SampledValue::Translation(Vector3::new(2.0, 0.0, 0.0)))
+        );
+        assert_eq!(
+            key.sample(1.5, WrapMode::Clamp),
+            Some(SampledValue::Translation(Vector3::new(3.0, 0.0, 0.0)))
+        );
+    }
+
+    mod animations {
+        use crate::{AnimationKeys, Bsp, Chunk, Decode, WrapMode};
+        use std::{fs::File, io::BufReader};
+        use test_case::test_case;
+
+        fn extent<'a>(coordinates: impl Iterator<Item = &'a (f32, f32)>) -> f32 {
+            coordinates.fold(0.0, |extent, (u, v)| extent.max(u.abs()).max(v.abs()))
+        }
+
+        /// Checks the assumed binding and scale of UV tracks against real files: every track
+        /// animates whole sets of the part at `material_block_index`, and its coordinates are of
+        /// the same magnitude as the part's own.
+        #[test_case("aether" ; "aether")]
+        #[test_case("boo" ; "boo")]
+        #[test_case("moonscream" ; "moonscream")]
+        fn matches_assumed_uv_layout(ghost: &str) {
+            let decode = |path: String| {
+                Bsp::decode(&mut BufReader::new(File::open(path).unwrap()), ()).unwrap()
+            };
+            let model = decode(format!("assets/ghosts/{}.bsp", ghost));
+            let animations = decode(format!("assets/ghosts/animations/{}_anims.bsp", ghost));
+            let model_parts = model
+                .chunks
+                .iter()
+                .filter_map(|chunk| match chunk {
+                    Chunk::SPMesh(model_part) => Some(model_part),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            for key in animations.chunks.iter().filter_map(|chunk| match chunk {
+                Chunk::AnimationKey(key) if matches!(key.keys, AnimationKeys::Uvs(_)) => Some(key),
+                _ => None,
+            }) {
+                let model_part = model_parts[key.material_block_index as usize];
+                let animated = key
+                    .key_times()
+                    .iter()
+                    .map(|time| {
+                        let uvs = key.animate_uvs(model_part, *time, WrapMode::Clamp).unwrap();
+
+                        extent(uvs.iter().flatten())
+                    })
+                    .fold(0.0, f32::max);
+                let own = extent(model_part.vertices.iter().flat_map(|vertex| &vertex.uvs));
+
+                assert!(animated <= 8.0 * own.max(1.0));
+                assert!(8.0 * animated >= own.min(1.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use crate::{
+    hash, AnimationDictionary, AnimationKey, AnimationKeys, Bsp, Chunk, Clips, Quaternion,
+    SampledValue, Vector3, VisibilityState, WrapMode,
+};
+use std::collections::BTreeMap;
+
+/// The clips of an `_anims.bsp` file together with the base poses they start from.
+#[derive(Clone, Debug, Default)]
+pub struct AnimationLibrary<'a> {
+    pub dictionary: Option<&'a AnimationDictionary>,
+    pub clips: Vec<AnimationClip<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnimationLibraryError {
+    /// The `sequence_count` of the clips adds up to `expected` tracks, but the file has `actual`
+    /// `AnimationKey` chunks.
+    KeyCountMismatch { expected: usize, actual: usize },
+}
+
+/// A clip and its key tracks.
+#[derive(Clone, Debug)]
+pub struct AnimationClip<'a> {
+    pub clip: &'a Clips,
+    pub keys: Vec<&'a AnimationKey>,
+    bone_tracks: BTreeMap<u32, BoneTracks<'a>>,
+}
+
+/// The tracks of one bone, identified by their shared `target_hash`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoneTracks<'a> {
+    pub rotation: Option<&'a AnimationKey>,
+    pub translation: Option<&'a AnimationKey>,
+    pub visibility: Option<&'a AnimationKey>,
+}
+
+/// The local transform of a bone at some time of a clip.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BonePose {
+    pub rotation: Quaternion,
+    pub translation: Vector3,
+    pub visibility: VisibilityState,
+}
+
+impl<'a> AnimationLibrary<'a> {
+    /// Groups the chunks of `bsp`. Clips own `sequence_count` tracks each, taken in file order
+    /// from the `AnimationKey` chunks, so every track must belong to exactly one clip.
+    pub fn new(bsp: &'a Bsp) -> Result<Self, AnimationLibraryError> {
+        let mut dictionary = None;
+        let mut clips = Vec::new();
+        let mut keys = Vec::new();
+
+        for chunk in &bsp.chunks {
+            match chunk {
+                Chunk::AnimLib(animation_dictionary) if dictionary.is_none() => {
+                    dictionary = Some(animation_dictionary)
+                }
+                Chunk::Animation(clip) => clips.push(clip),
+                Chunk::AnimationKey(key) => keys.push(key),
+                _ => (),
+            }
+        }
+
+        let expected = clips
+            .iter()
+            .map(|clip| clip.sequence_count.max(0) as usize)
+            .sum();
+
+        if expected != keys.len() {
+            return Err(AnimationLibraryError::KeyCountMismatch {
+                expected,
+                actual: keys.len(),
+            });
+        }
+
+        let mut keys = keys.into_iter();
+        let clips = clips
+            .into_iter()
+            .map(|clip| {
+                AnimationClip::new(
+                    clip,
+                    keys.by_ref()
+                        .take(clip.sequence_count.max(0) as usize)
+                        .collect(),
+                )
+            })
+            .collect();
+
+        Ok(Self { dictionary, clips })
+    }
+
+    /// Finds a clip by name, ignoring ASCII case, or by the hash of the name.
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip<'a>> {
+        let name_hash = hash(name.as_bytes());
+
+        self.clips
+            .iter()
+            .find(|clip| clip.name().eq_ignore_ascii_case(name) || clip.clip.name_hash == name_hash)
+    }
+
+    pub fn clip_names(&self) -> impl Iterator<Item = &str> {
+        self.clips.iter().map(|clip| clip.name())
+    }
+
+    /// The pose of the bone at `index` in the base poses of the dictionary.
+    pub fn base_pose(&self, index: usize) -> BonePose {
+        self.dictionary
+            .and_then(|dictionary| dictionary.base_poses.get(index))
+            .map(|base_pose| BonePose {
+                rotation: base_pose.rotation.dequantize(),
+                translation: base_pose.position,
+                visibility: VisibilityState::On,
+            })
+            .unwrap_or(BonePose {
+                rotation: Quaternion::identity(),
+                translation: Vector3::default(),
+                visibility: VisibilityState::On,
+            })
+    }
+
+    /// Samples the bone with the given track target at `time`, falling back to its base pose for
+    /// every component the clip does not animate. The base pose is the one the clip's scaffold
+    /// assigns to `target_hash`, or else the one of `bone_index`.
+    ///
+    /// `time` is measured from the start of the clip, on the same scale as `key_times`, whatever
+    /// the clip's `minimum_time`, so the glTF exporter writes `key_times` unchanged.
+    pub fn sample_bone(
+        &self,
+        clip: &AnimationClip,
+        target_hash: u32,
+        bone_index: usize,
+        time: f32,
+        wrap_mode: WrapMode,
+    ) -> BonePose {
+        let mut pose = self.base_pose(clip.base_pose_index(target_hash).unwrap_or(bone_index));
+        let tracks = clip
+            .bone_tracks
+            .get(&target_hash)
+            .copied()
+            .unwrap_or_default();
+        for key in [tracks.rotation, tracks.translation, tracks.visibility]
+            .into_iter()
+            .flatten()
+        {
+            match key.sample(time, wrap_mode) {
+                Some(SampledValue::Rotation(rotation)) => pose.rotation = rotation,
+                Some(SampledValue::Translation(translation)) => pose.translation = translation,
+                Some(SampledValue::Visibility(visibility)) => pose.visibility = visibility,
+                _ => (),
+            }
+        }
+
+        pose
+    }
+}
+
+impl<'a> AnimationClip<'a> {
+    pub fn new(clip: &'a Clips, keys: Vec<&'a AnimationKey>) -> Self {
+        let bone_tracks = group_bone_tracks(&keys);
+
+        Self {
+            clip,
+            keys,
+            bone_tracks,
+        }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.clip.name.trim_end_matches('\0')
+    }
+
+    /// The length of the clip, from its stored time range or else from its longest track.
+    pub fn duration(&self) -> f32 {
+        let duration = self.clip.maximum_time - self.clip.minimum_time;
+
+        if duration > 0.0 {
+            duration
+        } else {
+            self.keys
+                .iter()
+                .map(|key| key.duration())
+                .fold(0.0, f32::max)
+        }
+    }
+
+    /// The rotation, translation and visibility tracks, grouped by `target_hash`.
+    pub fn bone_tracks(&self) -> &BTreeMap<u32, BoneTracks<'a>> {
+        &self.bone_tracks
+    }
+
+    /// The index in the dictionary base poses of the pose of the bone `target_hash` names.
+    ///
+    /// The scaffold of a clip is assumed to list one `(hash1, hash2)` pair per base pose of the
+    /// dictionary, in the same order, either hash naming the bone the pose belongs to.
+    pub fn base_pose_index(&self, target_hash: u32) -> Option<usize> {
+        self.clip
+            .base_poses
+            .iter()
+            .position(|scaffold| scaffold.hash1 == target_hash || scaffold.hash2 == target_hash)
+    }
+
+    /// The shape and UV tracks, which animate model parts rather than bones.
+    pub fn part_tracks(&self) -> impl Iterator<Item = &'a AnimationKey> + '_ {
+        self.keys
+            .iter()
+            .copied()
+            .filter(|key| matches!(key.keys, AnimationKeys::Shapes(_) | AnimationKeys::Uvs(_)))
+    }
+}
+
+/// Groups the rotation, translation and visibility tracks by `target_hash`. The first track of
+/// each kind wins.
+fn group_bone_tracks<'a>(keys: &[&'a AnimationKey]) -> BTreeMap<u32, BoneTracks<'a>> {
+    let mut bone_tracks = BTreeMap::<u32, BoneTracks>::new();
+
+    for key in keys {
+        let tracks = bone_tracks.entry(key.target_hash).or_default();
+        let track = match key.keys {
+            AnimationKeys::Rotations(_) => &mut tracks.rotation,
+            AnimationKeys::Translations(_) => &mut tracks.translation,
+            AnimationKeys::VisibilityStates(_) => &mut tracks.visibility,
+            _ => continue,
+        };
+
+        track.get_or_insert(key);
+    }
+
+    bone_tracks.retain(|_, tracks| {
+        tracks.rotation.is_some() || tracks.translation.is_some() || tracks.visibility.is_some()
+    });
+
+    bone_tracks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BasePose, Interpolation, QuantizedQuaternion, Scaffold};
+
+    fn clip(name: &str, sequence_count: i32, base_poses: Vec<Scaffold>) -> Chunk {
+        Chunk::Animation(Clips {
+            name_hash: hash(name.as_bytes()),
+            // Key times start at zero regardless of the stored range.
+            minimum_time: 1.0,
+            maximum_time: 3.0,
+            base_poses,
+            sequence_count,
+            name: name.into(),
+        })
+    }
+
+    fn translation_key(target_hash: u32) -> Chunk {
+        Chunk::AnimationKey(AnimationKey {
+            type_: 1,
+            target_hash,
+            time_step: 1.0,
+            key_count: 2,
+            material_block_index: 0,
+            bounding_box_maximum: None,
+            interpolation_type: Interpolation::Linear,
+            times: None,
+            keys: AnimationKeys::Translations(vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+            ]),
+            adaptive_differential_pulse_code_modulation: None,
+        })
+    }
+
+    #[test]
+    fn groups_keys_into_clips() {
+        let base_pose = |y| BasePose {
+            rotation: QuantizedQuaternion::new(0, 0, 0, i16::MAX),
+            position: Vector3::new(0.0, y, 0.0),
+        };
+        let scaffold = |hash1| Scaffold { hash1, hash2: 0 };
+        let bsp = Bsp {
+            chunks: vec![
+                Chunk::AnimLib(AnimationDictionary {
+                    base_poses: vec![base_pose(1.0), base_pose(3.0)],
+                    clip_count: 2,
+                }),
+                clip("idle", 1, Vec::new()),
+                clip("walk", 2, vec![scaffold(9), scaffold(8)]),
+                translation_key(5),
+                translation_key(7),
+                translation_key(9),
+            ],
+        };
+        let library = AnimationLibrary::new(&bsp).unwrap();
+        let walk = library.clip("WALK").unwrap();
+
+        assert_eq!(
+            library.clip_names().collect::<Vec<_>>(),
+            vec!["idle", "walk"]
+        );
+        assert_eq!(library.clip("idle").unwrap().keys.len(), 1);
+        assert_eq!(walk.duration(), 2.0);
+        assert_eq!(walk.bone_tracks().keys().collect::<Vec<_>>(), vec![&7, &9]);
+
+        let pose = library.sample_bone(walk, 7, 0, 0.5, WrapMode::Clamp);
+
+        assert_eq!(pose.translation, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(pose.rotation, Quaternion::identity());
+        assert_eq!(
+            library
+                .sample_bone(walk, 8, 0, 0.5, WrapMode::Clamp)
+                .translation,
+            Vector3::new(0.0, 3.0, 0.0)
+        );
+        assert_eq!(
+            library
+                .sample_bone(walk, 6, 0, 0.5, WrapMode::Clamp)
+                .translation,
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+
+        let bsp = Bsp {
+            chunks: vec![clip("idle", 2, Vec::new()), translation_key(5)],
+        };
+
+        assert_eq!(
+            AnimationLibrary::new(&bsp).unwrap_err(),
+            AnimationLibraryError::KeyCountMismatch {
+                expected: 2,
+                actual: 1
+            }
+        );
+    }
+
+    mod animations {
+        use crate::{AnimationLibrary, Bsp, Chunk, Decode};
+        use std::{fs::File, io::BufReader};
+        use test_case::test_case;
+
+        /// Checks the assumed meaning of `sequence_count` and of the scaffold against real
+        /// animation files: the library assembles, and no scaffold lists more poses than the
+        /// dictionary holds.
+        #[test_case("aether_anims" ; "aether_anims")]
+        #[test_case("boo_anims" ; "boo_anims")]
+        #[test_case("moonscream_anims" ; "moonscream_anims")]
+        fn matches_assumed_layout(asset: &str) {
+            let bsp = Bsp::decode(
+                &mut BufReader::new(
+                    File::open(format!("assets/ghosts/animations/{}.bsp", asset)).unwrap(),
+                ),
+                (),
+            )
+            .unwrap();
+            let library = AnimationLibrary::new(&bsp).unwrap();
+            let base_pose_count = bsp
+                .chunks
+                .iter()
+                .find_map(|chunk| match chunk {
+                    Chunk::AnimLib(dictionary) => Some(dictionary.base_poses.len()),
+                    _ => None,
+                })
+                .unwrap_or(0);
+
+            assert!(library
+                .clips
+                .iter()
+                .all(|clip| clip.clip.base_poses.len() <= base_pose_count));
+        }
+    }
+}
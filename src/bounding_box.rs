@@ -7,6 +7,28 @@ pub struct BoundingBox {
     pub infimum: Vector3,
 }
 
+impl BoundingBox {
+    pub fn contains(&self, point: &Vector3) -> bool {
+        (self.infimum.x..=self.supremum.x).contains(&point.x)
+            && (self.infimum.y..=self.supremum.y).contains(&point.y)
+            && (self.infimum.z..=self.supremum.z).contains(&point.z)
+    }
+
+    /// Whether the boxes overlap, touching counting as overlapping.
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.infimum.x <= other.supremum.x
+            && other.infimum.x <= self.supremum.x
+            && self.infimum.y <= other.supremum.y
+            && other.infimum.y <= self.supremum.y
+            && self.infimum.z <= other.supremum.z
+            && other.infimum.z <= self.supremum.z
+    }
+
+    pub fn center(&self) -> Vector3 {
+        self.infimum.lerp(&self.supremum, 0.5)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct OrientedBoundingBox {
     pub center: Vector3,
@@ -1,6 +1,11 @@
-use crate::{Chunk, ChunkHeader, Decode, DecodeError, PeekableReader, PositionTracker};
+use crate::{
+    Chunk, ChunkHeader, Decode, DecodeError, Mesh, ModelPart, PeekableReader, PositionTracker,
+};
 use flate2::read::GzDecoder;
-use std::io::{ErrorKind, Read};
+use std::{
+    io::{ErrorKind, Read},
+    iter::Peekable,
+};
 
 const GZIP_MAGIC_NUMBER: [u8; 2] = [0x1f, 0x8b];
 
@@ -64,6 +69,37 @@ impl Decode for Bsp {
     }
 }
 
+/// Takes the `SPMesh` chunks following a `ModelGroup` chunk from `chunks`, which hold the
+/// `material_blocks_count` material blocks of `mesh`.
+pub fn take_model_parts<'a>(
+    mesh: &Mesh,
+    chunks: &mut Peekable<impl Iterator<Item = &'a Chunk>>,
+) -> Vec<&'a ModelPart> {
+    let mut model_parts = Vec::with_capacity(mesh.material_blocks_count as usize);
+
+    while model_parts.len() < mesh.material_blocks_count as usize {
+        match chunks.next_if(|chunk| matches!(chunk, Chunk::SPMesh(_))) {
+            Some(Chunk::SPMesh(model_part)) => model_parts.push(model_part),
+            _ => break,
+        }
+    }
+
+    model_parts
+}
+
+/// Decodes `assets/levels/{asset}.bsp`, for tests checking decoded data against the layouts
+/// the query modules assume.
+#[cfg(test)]
+pub(crate) fn decode_level(asset: &str) -> Bsp {
+    use std::{fs::File, io::BufReader};
+
+    Bsp::decode(
+        &mut BufReader::new(File::open(format!("assets/levels/{}.bsp", asset)).unwrap()),
+        (),
+    )
+    .unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     mod ghosts {
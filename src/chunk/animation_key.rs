@@ -3,11 +3,17 @@ use crate::{BoundingBox, Decode, DecodeError, QuantizedQuaternion, Vector3};
 use num_enum::TryFromPrimitive;
 use std::io::Read;
 
+pub const UV_KEY_SET_COUNT: usize = 2;
+
+/// Assumed rather than documented; `matches_assumed_uv_layout` checks it against real files.
+const UV_QUANTIZATION_SCALE: f32 = 4096.0;
+
 #[derive(Clone, Debug)]
 pub struct AnimationKey {
     pub type_: i32,
     pub target_hash: u32,
     pub time_step: f32,
+    pub key_count: i32,
     pub material_block_index: u16,
     pub bounding_box_maximum: Option<BoundingBox>,
     pub interpolation_type: Interpolation,
@@ -66,35 +72,8 @@ impl Decode for AnimationKey {
             }
             AnimationKeyType::Uv => {
                 let uvs = (0..key_count)
-                    .into_iter()
-                    .map(|_| {
-                        (0..2)
-                            .into_iter()
-                            .map(|_| {
-                                let uv_count = u16::decode(reader, ()).unwrap();
-
-                                let us = (0..uv_count)
-                                    .into_iter()
-                                    .map(|_| u16::decode(reader, ()))
-                                    .collect::<Result<Vec<_>, _>>()
-                                    .unwrap();
-
-                                let vs = (0..uv_count)
-                                    .into_iter()
-                                    .map(|_| u16::decode(reader, ()))
-                                    .collect::<Result<Vec<_>, _>>()
-                                    .unwrap();
-
-                                us.into_iter()
-                                    .zip(vs.into_iter())
-                                    .map(|(u, v)| Uv::new(u, v))
-                                    .collect::<Vec<_>>()
-                            })
-                            .flatten()
-                            .collect::<Vec<_>>()
-                    })
-                    .flatten()
-                    .collect::<Vec<_>>();
+                    .map(|_| UvKey::decode(reader, ()))
+                    .collect::<Result<Vec<_>, _>>()?;
 
                 AnimationKeys::Uvs(uvs)
             }
@@ -114,6 +93,7 @@ impl Decode for AnimationKey {
             type_,
             target_hash,
             time_step,
+            key_count,
             material_block_index,
             bounding_box_maximum,
             interpolation_type,
@@ -152,7 +132,7 @@ pub enum AnimationKeys {
     Rotations(Vec<QuantizedQuaternion<i32>>),
     Translations(Vec<Vector3>),
     Shapes(Vec<Shape>),
-    Uvs(Vec<Uv>),
+    Uvs(Vec<UvKey>),
     VisibilityStates(Vec<VisibilityState>),
 }
 
@@ -260,19 +240,57 @@ pub struct NotKeyFrameNormals {
     pub elements: Vec<u16>,
 }
 
-#[derive(Clone, Debug)]
+/// The animated coordinates of one key, for each of the two UV sets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UvKey {
+    pub uv_sets: [Vec<Uv>; UV_KEY_SET_COUNT],
+}
+
+impl Decode for UvKey {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        let mut uv_sets: [Vec<Uv>; UV_KEY_SET_COUNT] = Default::default();
+
+        for uvs in &mut uv_sets {
+            let uv_count = u16::decode(reader, ())?;
+            let us = (0..uv_count)
+                .map(|_| u16::decode(reader, ()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let vs = (0..uv_count)
+                .map(|_| u16::decode(reader, ()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            *uvs = us
+                .into_iter()
+                .zip(vs)
+                .map(|(u, v)| Uv::dequantize(u, v))
+                .collect();
+        }
+
+        Ok(Self { uv_sets })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Uv {
-    pub u: u16,
-    pub v: u16,
+    pub u: f32,
+    pub v: f32,
 }
 
 impl Uv {
-    pub fn new(u: u16, v: u16) -> Self {
+    pub fn new(u: f32, v: f32) -> Self {
         Self { u, v }
     }
+
+    /// Converts coordinates stored as signed fixed point numbers with 12 fractional bits.
+    pub fn dequantize(u: u16, v: u16) -> Self {
+        Self::new(
+            u as i16 as f32 / UV_QUANTIZATION_SCALE,
+            v as i16 as f32 / UV_QUANTIZATION_SCALE,
+        )
+    }
 }
 
-#[derive(Clone, Debug, TryFromPrimitive, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, TryFromPrimitive, PartialEq, Eq)]
 #[repr(u8)]
 pub enum VisibilityState {
     Off = 0,
@@ -5,6 +5,7 @@ pub struct CameraProjection {
     pub type_: i32,
     pub near_z: f32,
     pub far_z: f32,
+    /// The vertical field of view in degrees, like `gluPerspective`'s `fovy`.
     pub angle_y: f32,
     pub rectangle: Rectangle,
 }
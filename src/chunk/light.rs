@@ -9,6 +9,7 @@ pub struct Light {
     pub flags: u32,
     pub radius: f32,
     pub light_color: Rgba,
+    /// The full angle of a spot light's cone in radians, like D3D's `Phi`.
     pub cone_angle: f32,
     pub photon_light_abs_scale: f32,
     pub light_switch_layer_index: Option<u32>,
@@ -1,10 +1,13 @@
 use std::io::Read;
 
-use crate::{Decode, DecodeError, I32Encoded, Matrix, NullTerminated, Rgba};
+use crate::{
+    Decode, DecodeError, I32Encoded, Matrix, NullTerminated, Rgba, TextureAddress, TextureFilter,
+    TextureFormat,
+};
 
 #[derive(Clone, Debug, Decode)]
 pub struct Material {
-    pub flags: u32,
+    pub flags: MaterialFlags,
     pub name_hash: u32,
     pub additive_lighting_model: bool,
     #[encoding(i32)]
@@ -12,30 +15,59 @@ pub struct Material {
     #[encoding(i32)]
     pub specular: Rgba,
     pub power: f32,
-    pub shading_mode: i32,
+    pub shading_mode: ShadingMode,
     pub blend: bool,
     pub blend_modes: BlendModes,
     pub alpha_test: bool,
     pub alpha_test_mode: AlphaTestMode,
     pub depth_buffer_write: bool,
-    pub depth_buffer_comparison_mode: i32,
+    pub depth_buffer_comparison_mode: ComparisonFunction,
     pub material_hash: u32,
     pub owner: u32,
     pub color_buffer_write: u32,
     pub textures: [MaterialTexture; 5],
     pub matrices: [Option<Matrix>; 5],
-    pub generators: [i32; 5],
-    pub envmap_type: i32,
+    pub generators: [TextureGenerator; 5],
+    pub envmap_type: EnvmapType,
     pub planar_sheer_envmap_distance: f32,
 }
 
+/// An opaque, untextured white material with Direct3D's default depth state.
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            flags: MaterialFlags::default(),
+            name_hash: 0,
+            additive_lighting_model: false,
+            color: Rgba::new(255, 255, 255, 255),
+            specular: Rgba::default(),
+            power: 0.0,
+            shading_mode: ShadingMode::Gouraud,
+            blend: false,
+            blend_modes: BlendModes::default(),
+            alpha_test: false,
+            alpha_test_mode: AlphaTestMode::default(),
+            depth_buffer_write: true,
+            depth_buffer_comparison_mode: ComparisonFunction::LessEqual,
+            material_hash: 0,
+            owner: 0,
+            color_buffer_write: 1,
+            textures: Default::default(),
+            matrices: Default::default(),
+            generators: [TextureGenerator::PassThrough; 5],
+            envmap_type: EnvmapType::None,
+            planar_sheer_envmap_distance: 0.0,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct MaterialTexture {
     pub uv_set: u32,
     pub name: String,
-    pub format: Option<i32>,
-    pub filter: Option<i32>,
-    pub address: Option<i32>,
+    pub format: Option<TextureFormat>,
+    pub filter: Option<TextureFilter>,
+    pub address: Option<TextureAddress>,
     pub mask_name: Option<String>,
     pub border_color: Option<Rgba>,
     pub hash: Option<u32>,
@@ -47,9 +79,9 @@ impl Decode for MaterialTexture {
         let name = I32Encoded::<NullTerminated<String>>::decode(reader, ())?;
 
         let (format, filter, address, mask_name, border_color, hash) = if name.len() > 0 {
-            let format = i32::decode(reader, ())?;
-            let filter = i32::decode(reader, ())?;
-            let address = i32::decode(reader, ())?;
+            let format = TextureFormat::decode(reader, ())?;
+            let filter = TextureFilter::decode(reader, ())?;
+            let address = TextureAddress::decode(reader, ())?;
             let mask_name = I32Encoded::<NullTerminated<String>>::decode(reader, ())?;
             let border_color = I32Encoded::<Rgba>::decode(reader, ())?;
             let hash = u32::decode(reader, ())?;
@@ -79,14 +111,406 @@ impl Decode for MaterialTexture {
     }
 }
 
-#[derive(Clone, Debug, Decode, Default)]
+#[derive(Clone, Debug, Decode)]
 pub struct BlendModes {
-    pub source_mode: i32,
-    pub destination_mode: i32,
+    pub source_mode: BlendFactor,
+    pub destination_mode: BlendFactor,
+}
+
+impl Default for BlendModes {
+    fn default() -> Self {
+        Self {
+            source_mode: BlendFactor::One,
+            destination_mode: BlendFactor::Zero,
+        }
+    }
 }
 
-#[derive(Clone, Debug, Decode, Default)]
+#[derive(Clone, Debug, Decode)]
 pub struct AlphaTestMode {
-    pub comparision_function: i32,
+    pub comparision_function: ComparisonFunction,
     pub reference: f32,
 }
+
+impl AlphaTestMode {
+    /// The reference value in `[0, 1]`. Materials store Direct3D's byte sized `D3DRS_ALPHAREF`
+    /// as a float.
+    pub fn normalized_reference(&self) -> f32 {
+        (self.reference / 255.0).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for AlphaTestMode {
+    fn default() -> Self {
+        Self {
+            comparision_function: ComparisonFunction::Always,
+            reference: 0.0,
+        }
+    }
+}
+
+/// Material flags. Only `TWO_SIDED` has been identified, the other bits are kept as is and can
+/// be queried by mask.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MaterialFlags(pub u32);
+
+impl MaterialFlags {
+    /// Faces are drawn from both sides. Assumed to be the lowest bit, which
+    /// `matches_assumed_flags` checks against the materials of real levels.
+    pub const TWO_SIDED: Self = Self(0x1);
+    /// Every identified bit.
+    pub const KNOWN: Self = Self::TWO_SIDED;
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// The bits that are set but have not been identified.
+    pub fn unknown_bits(&self) -> u32 {
+        self.0 & !Self::KNOWN.0
+    }
+
+    /// Iterates over the masks of the bits that are set.
+    pub fn iter(&self) -> impl Iterator<Item = u32> {
+        let bits = self.0;
+
+        (0..u32::BITS)
+            .map(|bit| 1 << bit)
+            .filter(move |mask| bits & mask != 0)
+    }
+}
+
+impl Decode for MaterialFlags {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self(u32::decode(reader, ())?))
+    }
+}
+
+/// Direct3D `D3DSHADEMODE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadingMode {
+    Flat,
+    Gouraud,
+    Phong,
+    Unknown(i32),
+}
+
+impl From<i32> for ShadingMode {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => Self::Flat,
+            2 => Self::Gouraud,
+            3 => Self::Phong,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<ShadingMode> for i32 {
+    fn from(mode: ShadingMode) -> Self {
+        match mode {
+            ShadingMode::Flat => 1,
+            ShadingMode::Gouraud => 2,
+            ShadingMode::Phong => 3,
+            ShadingMode::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for ShadingMode {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
+
+/// Direct3D `D3DBLEND`, the factor a color is multiplied by before blending.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SourceColor,
+    InverseSourceColor,
+    SourceAlpha,
+    InverseSourceAlpha,
+    DestinationAlpha,
+    InverseDestinationAlpha,
+    DestinationColor,
+    InverseDestinationColor,
+    SourceAlphaSaturate,
+    BothSourceAlpha,
+    BothInverseSourceAlpha,
+    Unknown(i32),
+}
+
+impl From<i32> for BlendFactor {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => Self::Zero,
+            2 => Self::One,
+            3 => Self::SourceColor,
+            4 => Self::InverseSourceColor,
+            5 => Self::SourceAlpha,
+            6 => Self::InverseSourceAlpha,
+            7 => Self::DestinationAlpha,
+            8 => Self::InverseDestinationAlpha,
+            9 => Self::DestinationColor,
+            10 => Self::InverseDestinationColor,
+            11 => Self::SourceAlphaSaturate,
+            12 => Self::BothSourceAlpha,
+            13 => Self::BothInverseSourceAlpha,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<BlendFactor> for i32 {
+    fn from(factor: BlendFactor) -> Self {
+        match factor {
+            BlendFactor::Zero => 1,
+            BlendFactor::One => 2,
+            BlendFactor::SourceColor => 3,
+            BlendFactor::InverseSourceColor => 4,
+            BlendFactor::SourceAlpha => 5,
+            BlendFactor::InverseSourceAlpha => 6,
+            BlendFactor::DestinationAlpha => 7,
+            BlendFactor::InverseDestinationAlpha => 8,
+            BlendFactor::DestinationColor => 9,
+            BlendFactor::InverseDestinationColor => 10,
+            BlendFactor::SourceAlphaSaturate => 11,
+            BlendFactor::BothSourceAlpha => 12,
+            BlendFactor::BothInverseSourceAlpha => 13,
+            BlendFactor::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for BlendFactor {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
+
+/// Direct3D `D3DCMPFUNC`, used by both the depth and the alpha test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComparisonFunction {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+    Unknown(i32),
+}
+
+impl ComparisonFunction {
+    /// Returns `None` when the function is unknown.
+    pub fn compare(&self, value: f32, reference: f32) -> Option<bool> {
+        Some(match self {
+            Self::Never => false,
+            Self::Less => value < reference,
+            Self::Equal => value == reference,
+            Self::LessEqual => value <= reference,
+            Self::Greater => value > reference,
+            Self::NotEqual => value != reference,
+            Self::GreaterEqual => value >= reference,
+            Self::Always => true,
+            Self::Unknown(_) => return None,
+        })
+    }
+}
+
+impl From<i32> for ComparisonFunction {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => Self::Never,
+            2 => Self::Less,
+            3 => Self::Equal,
+            4 => Self::LessEqual,
+            5 => Self::Greater,
+            6 => Self::NotEqual,
+            7 => Self::GreaterEqual,
+            8 => Self::Always,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<ComparisonFunction> for i32 {
+    fn from(function: ComparisonFunction) -> Self {
+        match function {
+            ComparisonFunction::Never => 1,
+            ComparisonFunction::Less => 2,
+            ComparisonFunction::Equal => 3,
+            ComparisonFunction::LessEqual => 4,
+            ComparisonFunction::Greater => 5,
+            ComparisonFunction::NotEqual => 6,
+            ComparisonFunction::GreaterEqual => 7,
+            ComparisonFunction::Always => 8,
+            ComparisonFunction::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for ComparisonFunction {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
+
+/// Direct3D `D3DTSS_TCI_*`, how the texture coordinates of a stage are produced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureGenerator {
+    /// The coordinates of the stage's `uv_set` are used as is.
+    PassThrough,
+    CameraSpaceNormal,
+    CameraSpacePosition,
+    CameraSpaceReflectionVector,
+    SphereMap,
+    Unknown(i32),
+}
+
+impl From<i32> for TextureGenerator {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::PassThrough,
+            0x10000 => Self::CameraSpaceNormal,
+            0x20000 => Self::CameraSpacePosition,
+            0x30000 => Self::CameraSpaceReflectionVector,
+            0x40000 => Self::SphereMap,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<TextureGenerator> for i32 {
+    fn from(generator: TextureGenerator) -> Self {
+        match generator {
+            TextureGenerator::PassThrough => 0,
+            TextureGenerator::CameraSpaceNormal => 0x10000,
+            TextureGenerator::CameraSpacePosition => 0x20000,
+            TextureGenerator::CameraSpaceReflectionVector => 0x30000,
+            TextureGenerator::SphereMap => 0x40000,
+            TextureGenerator::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for TextureGenerator {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
+
+/// The kind of environment map applied by the material, if any. `Planar` projects the
+/// environment onto a plane `planar_sheer_envmap_distance` away from the camera.
+///
+/// The numbering is not documented anywhere; `matches_assumed_encoding` checks it against the
+/// materials of real levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnvmapType {
+    None,
+    Spherical,
+    Cubic,
+    Planar,
+    Unknown(i32),
+}
+
+impl From<i32> for EnvmapType {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::Spherical,
+            2 => Self::Cubic,
+            3 => Self::Planar,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<EnvmapType> for i32 {
+    fn from(envmap_type: EnvmapType) -> Self {
+        match envmap_type {
+            EnvmapType::None => 0,
+            EnvmapType::Spherical => 1,
+            EnvmapType::Cubic => 2,
+            EnvmapType::Planar => 3,
+            EnvmapType::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for EnvmapType {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod levels {
+        use crate::{bsp::decode_level, Chunk, EnvmapType, MaterialFlags};
+        use test_case::test_case;
+
+        /// Checks the assumed encodings against the materials of real levels: alpha references
+        /// are whole bytes, every environment map type is known, and only planar maps set a
+        /// plane distance.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_encoding(asset: &str) {
+            for chunk in &decode_level(asset).chunks {
+                let Chunk::MaterialObj(material) = chunk else {
+                    continue;
+                };
+                let reference = material.alpha_test_mode.reference;
+
+                assert!(
+                    (0.0..=255.0).contains(&reference) && reference.fract() == 0.0,
+                    "{material:?}"
+                );
+                assert!(
+                    !matches!(material.envmap_type, EnvmapType::Unknown(_)),
+                    "{material:?}"
+                );
+                assert!(
+                    material.planar_sheer_envmap_distance == 0.0
+                        || material.envmap_type == EnvmapType::Planar,
+                    "{material:?}"
+                );
+            }
+        }
+
+        /// Checks the assumed `TWO_SIDED` bit against the materials of real levels: it is set on
+        /// some of them but not all, and on at least one of the alpha tested materials used for
+        /// foliage and fences, which are seen from both sides.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_flags(asset: &str) {
+            let flags = decode_level(asset)
+                .chunks
+                .iter()
+                .filter_map(|chunk| match chunk {
+                    Chunk::MaterialObj(material) => Some((
+                        material.alpha_test,
+                        material.flags.contains(MaterialFlags::TWO_SIDED),
+                    )),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            assert!(flags.iter().any(|(_, two_sided)| !two_sided));
+            assert!(flags
+                .iter()
+                .any(|(alpha_test, two_sided)| *alpha_test && *two_sided));
+        }
+    }
+}
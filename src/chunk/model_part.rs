@@ -10,7 +10,7 @@ const HAS_WEIGHT: u32 = 1 << 12;
 const HAS_INDICES: u32 = 1 << 13;
 const UV_COUNT_MASK: u32 = 0xFF;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct ModelPart {
     pub read_access_flags: u32,
     pub vertex_read_flags: u32,
@@ -97,7 +97,7 @@ impl Decode for ModelPart {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Vertex {
     pub vertex: Option<Vector3>,
     pub normal: Option<Vector3>,
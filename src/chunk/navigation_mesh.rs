@@ -5,7 +5,8 @@ use std::io::Read;
 #[derive(Clone, Debug)]
 pub struct NavigationMesh {
     pub waypoints: Vec<Waypoint>,
-    pub links: Vec<Link>,
+    /// The outgoing links of each waypoint, by waypoint index.
+    pub links: Vec<Vec<Link>>,
 }
 
 impl Decode for NavigationMesh {
@@ -23,9 +24,16 @@ impl Decode for NavigationMesh {
             .map(|_| Waypoint::decode(reader, ()))
             .collect::<Result<Vec<_>, _>>()?;
         let links = (0..waypoint_count)
-            .into_iter()
-            .map(|_| Link::decode(reader, ()))
+            .map(|_| decode_links(reader))
             .collect::<Result<Vec<_>, _>>()?;
+        let decoded_links = links.iter().map(Vec::len).sum::<usize>();
+
+        if decoded_links != link_count as usize {
+            return Err(DecodeError::CountMismatch {
+                expected: link_count as usize,
+                actual: decoded_links,
+            });
+        }
 
         Ok(Self { waypoints, links })
     }
@@ -37,25 +45,78 @@ pub struct Waypoint {
     pub flags: u32,
 }
 
-#[derive(Clone, Debug)]
+/// A connection to another waypoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Link {
     pub waypoint_index: u32,
     pub flags: u32,
 }
 
-impl Decode for Link {
-    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
-        let mut waypoint_index = u32::decode(reader, ())?;
-        let mut flags = 0;
+/// Reads the links of one waypoint, stored as `(waypoint_index, flags)` pairs up to a
+/// `u32::MAX` waypoint index. The link count in the header counts the links of every waypoint.
+fn decode_links(reader: &mut impl Read) -> Result<Vec<Link>, DecodeError> {
+    let mut links = Vec::new();
 
-        while waypoint_index != u32::MAX {
-            flags = u32::decode(reader, ())?;
-            waypoint_index = u32::decode(reader, ())?;
+    loop {
+        let waypoint_index = u32::decode(reader, ())?;
+
+        if waypoint_index == u32::MAX {
+            return Ok(links);
         }
 
-        Ok(Self {
+        let flags = u32::decode(reader, ())?;
+
+        links.push(Link {
             waypoint_index,
             flags,
-        })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TERMINATOR: u32 = u32::MAX;
+
+    /// Two waypoints at the origin, the first linked to the second with flags 7.
+    fn encode(link_count: u32) -> Vec<u8> {
+        let header = [2, link_count];
+        let waypoints = [[0; 4], [0; 4]];
+        let links = [&[1, 7, TERMINATOR][..], &[TERMINATOR][..]];
+
+        header
+            .iter()
+            .chain(waypoints.iter().flatten())
+            .chain(links.iter().copied().flatten())
+            .flat_map(|word: &u32| word.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_every_link() {
+        let navigation_mesh = NavigationMesh::decode(&mut encode(1).as_slice(), ()).unwrap();
+
+        assert_eq!(
+            navigation_mesh.links,
+            vec![
+                vec![Link {
+                    waypoint_index: 1,
+                    flags: 7
+                }],
+                Vec::new()
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_link_count() {
+        assert!(matches!(
+            NavigationMesh::decode(&mut encode(2).as_slice(), ()),
+            Err(DecodeError::CountMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
     }
 }
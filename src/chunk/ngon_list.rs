@@ -37,6 +37,7 @@ pub struct NGonFace {
     pub vertex_index: u32,
     pub vertex_count: u32,
     pub flags: u32,
+    /// Not stored in the file; always 0 after decoding.
     pub test_count: u32,
 }
 
@@ -3,6 +3,8 @@ use std::io::Read;
 
 #[derive(Clone, Debug)]
 pub struct Occlusion {
+    /// Whether the branches store a single link per side.
+    pub is_plane_bsp: bool,
     pub branches: Vec<OcclusionBranch>,
     pub leaves: Vec<OcclusionLeaf>,
     pub has_occlusion_meshes: bool,
@@ -20,6 +22,7 @@ impl Decode for Occlusion {
         let has_occlusion_meshes = bool::decode(reader, ())?;
 
         Ok(Self {
+            is_plane_bsp,
             branches,
             leaves,
             has_occlusion_meshes,
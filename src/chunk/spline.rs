@@ -6,14 +6,14 @@ use crate::{Decode, DecodeError, Vector3};
 pub struct Spline {
     pub points: Vec<Vector3>,
     pub closed: bool,
-    pub type_: u32,
+    pub type_: SplineType,
 }
 
 impl Decode for Spline {
     fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
         let points_count = u32::decode(reader, ())?;
         let closed = bool::decode(reader, ())?;
-        let type_ = u32::decode(reader, ())?;
+        let type_ = SplineType::decode(reader, ())?;
         let points = (0..points_count as usize)
             .into_iter()
             .map(|_| Vector3::decode(reader, ()))
@@ -26,3 +26,44 @@ impl Decode for Spline {
         })
     }
 }
+
+/// How the points of a spline are joined. The numbering is assumed, and checked against the areas
+/// of the level assets in the `spline_curve` tests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplineType {
+    /// Straight segments between the points.
+    Polyline,
+    /// A curve through every point.
+    CatmullRom,
+    /// Cubic segments, each made of an end point, two control points and the next end point.
+    Bezier,
+    Unknown(u32),
+}
+
+impl From<u32> for SplineType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Polyline,
+            1 => Self::CatmullRom,
+            2 => Self::Bezier,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<SplineType> for u32 {
+    fn from(type_: SplineType) -> Self {
+        match type_ {
+            SplineType::Polyline => 0,
+            SplineType::CatmullRom => 1,
+            SplineType::Bezier => 2,
+            SplineType::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for SplineType {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(u32::decode(reader, ())?))
+    }
+}
@@ -86,6 +86,7 @@ impl Decode<u32> for SwitchableLightMap {
 #[derive(Clone, Debug)]
 pub struct LightMapUpdateBlock {
     pub layer_index: u32,
+    pub update_sub_rectangle: Option<Rectangle>,
     pub additive_data: Vec<Rgba>,
 }
 
@@ -93,12 +94,15 @@ impl Decode<i32> for LightMapUpdateBlock {
     fn decode(reader: &mut impl Read, pixels_to_read: i32) -> Result<Self, DecodeError> {
         let layer_index = u32::decode(reader, ())?;
 
-        let pixels = if pixels_to_read == 0 {
-            let update_sub_rectangle = Rectangle::decode(reader, ())?;
-
-            update_sub_rectangle.width * update_sub_rectangle.height
+        let update_sub_rectangle = if pixels_to_read == 0 {
+            Some(Rectangle::decode(reader, ())?)
         } else {
-            pixels_to_read
+            None
+        };
+
+        let pixels = match &update_sub_rectangle {
+            Some(update_sub_rectangle) => update_sub_rectangle.width * update_sub_rectangle.height,
+            None => pixels_to_read,
         };
 
         let additive_data = (0..pixels)
@@ -108,6 +112,7 @@ impl Decode<i32> for LightMapUpdateBlock {
 
         Ok(Self {
             layer_index,
+            update_sub_rectangle,
             additive_data,
         })
     }
@@ -10,22 +10,32 @@ pub struct Texture {
     pub mask_name: String,
     pub width: i32,
     pub height: i32,
-    pub filter: i32,
-    pub address: i32,
-    pub format: i32,
+    pub filter: TextureFilter,
+    pub address: TextureAddress,
+    pub format: TextureFormat,
     pub border_color: Rgba,
     pub pixels: Vec<Rgba>,
 }
 
+impl Texture {
+    /// Whether the alpha channel carries information, judged by the format and, for formats this
+    /// crate does not know, by the pixels themselves.
+    pub fn has_alpha(&self) -> bool {
+        self.format
+            .has_alpha()
+            .unwrap_or_else(|| self.pixels.iter().any(|pixel| pixel.a != u8::MAX))
+    }
+}
+
 impl Decode for Texture {
     fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
         let name = I32Encoded::<NullTerminated<String>>::decode(reader, ())?;
         let mask_name = I32Encoded::<NullTerminated<String>>::decode(reader, ())?;
         let width = i32::decode(reader, ())?;
         let height = i32::decode(reader, ())?;
-        let filter = i32::decode(reader, ())?;
-        let address = i32::decode(reader, ())?;
-        let format = i32::decode(reader, ())?;
+        let filter = TextureFilter::decode(reader, ())?;
+        let address = TextureAddress::decode(reader, ())?;
+        let format = TextureFormat::decode(reader, ())?;
         let border_color = I32Encoded::<Rgba>::decode(reader, ())?;
         let pixels = (0..width * height)
             .into_iter()
@@ -45,3 +55,181 @@ impl Decode for Texture {
         })
     }
 }
+
+/// The Direct3D surface format the texture is stored in on the target platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    R8G8B8,
+    A8R8G8B8,
+    X8R8G8B8,
+    R5G6B5,
+    X1R5G5B5,
+    A1R5G5B5,
+    A4R4G4B4,
+    A8,
+    P8,
+    L8,
+    Dxt1,
+    Dxt2,
+    Dxt3,
+    Dxt4,
+    Dxt5,
+    Unknown(i32),
+}
+
+impl TextureFormat {
+    /// Returns `None` when the format is unknown.
+    pub fn has_alpha(&self) -> Option<bool> {
+        match self {
+            Self::A8R8G8B8
+            | Self::A1R5G5B5
+            | Self::A4R4G4B4
+            | Self::A8
+            | Self::Dxt2
+            | Self::Dxt3
+            | Self::Dxt4
+            | Self::Dxt5 => Some(true),
+            Self::R8G8B8
+            | Self::X8R8G8B8
+            | Self::R5G6B5
+            | Self::X1R5G5B5
+            | Self::L8
+            | Self::Dxt1 => Some(false),
+            Self::P8 | Self::Unknown(_) => None,
+        }
+    }
+}
+
+impl From<i32> for TextureFormat {
+    fn from(value: i32) -> Self {
+        match value {
+            20 => Self::R8G8B8,
+            21 => Self::A8R8G8B8,
+            22 => Self::X8R8G8B8,
+            23 => Self::R5G6B5,
+            24 => Self::X1R5G5B5,
+            25 => Self::A1R5G5B5,
+            26 => Self::A4R4G4B4,
+            28 => Self::A8,
+            41 => Self::P8,
+            50 => Self::L8,
+            0x31545844 => Self::Dxt1,
+            0x32545844 => Self::Dxt2,
+            0x33545844 => Self::Dxt3,
+            0x34545844 => Self::Dxt4,
+            0x35545844 => Self::Dxt5,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<TextureFormat> for i32 {
+    fn from(format: TextureFormat) -> Self {
+        match format {
+            TextureFormat::R8G8B8 => 20,
+            TextureFormat::A8R8G8B8 => 21,
+            TextureFormat::X8R8G8B8 => 22,
+            TextureFormat::R5G6B5 => 23,
+            TextureFormat::X1R5G5B5 => 24,
+            TextureFormat::A1R5G5B5 => 25,
+            TextureFormat::A4R4G4B4 => 26,
+            TextureFormat::A8 => 28,
+            TextureFormat::P8 => 41,
+            TextureFormat::L8 => 50,
+            TextureFormat::Dxt1 => 0x31545844,
+            TextureFormat::Dxt2 => 0x32545844,
+            TextureFormat::Dxt3 => 0x33545844,
+            TextureFormat::Dxt4 => 0x34545844,
+            TextureFormat::Dxt5 => 0x35545844,
+            TextureFormat::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for TextureFormat {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    None,
+    Point,
+    Linear,
+    Anisotropic,
+    Unknown(i32),
+}
+
+impl From<i32> for TextureFilter {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::Point,
+            2 => Self::Linear,
+            3 => Self::Anisotropic,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<TextureFilter> for i32 {
+    fn from(filter: TextureFilter) -> Self {
+        match filter {
+            TextureFilter::None => 0,
+            TextureFilter::Point => 1,
+            TextureFilter::Linear => 2,
+            TextureFilter::Anisotropic => 3,
+            TextureFilter::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for TextureFilter {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
+
+/// How texture coordinates outside of `[0, 1]` are resolved. `Border` samples `border_color`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureAddress {
+    Wrap,
+    Mirror,
+    Clamp,
+    Border,
+    MirrorOnce,
+    Unknown(i32),
+}
+
+impl From<i32> for TextureAddress {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => Self::Wrap,
+            2 => Self::Mirror,
+            3 => Self::Clamp,
+            4 => Self::Border,
+            5 => Self::MirrorOnce,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<TextureAddress> for i32 {
+    fn from(address: TextureAddress) -> Self {
+        match address {
+            TextureAddress::Wrap => 1,
+            TextureAddress::Mirror => 2,
+            TextureAddress::Clamp => 3,
+            TextureAddress::Border => 4,
+            TextureAddress::MirrorOnce => 5,
+            TextureAddress::Unknown(value) => value,
+        }
+    }
+}
+
+impl Decode for TextureAddress {
+    fn decode(reader: &mut impl Read, _state: ()) -> Result<Self, DecodeError> {
+        Ok(Self::from(i32::decode(reader, ())?))
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{Bsp, CollisionWorld, Vector3};
+use std::collections::{HashMap, HashSet};
+
+/// The triangles referenced by a collision BSP, as an indexed mesh.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CollisionMesh {
+    pub positions: Vec<Vector3>,
+    /// Three indices into `positions` per triangle.
+    pub indices: Vec<[u32; 3]>,
+    /// The material of each triangle, in the same order as `indices`.
+    pub materials: Vec<CollisionMaterial>,
+}
+
+/// Where a collision triangle comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CollisionMaterial {
+    pub material_hash: u32,
+    pub material_block_index: u16,
+    pub face_index: u16,
+}
+
+impl CollisionWorld<'_> {
+    /// Resolves every face of the tree to its triangle. Faces shared by several leaves are
+    /// emitted once and vertices shared by several faces are welded. Faces that do not resolve
+    /// to a triangle are skipped.
+    pub fn triangle_mesh(&self) -> CollisionMesh {
+        let mut mesh = CollisionMesh::default();
+        let mut emitted = HashSet::new();
+        let mut vertices = HashMap::new();
+
+        for face in &self.collision.faces {
+            if !emitted.insert((face.material_block_index, face.face_index)) {
+                continue;
+            }
+
+            let Some((corners, positions)) = self.resolve(face) else {
+                continue;
+            };
+            let triangle = [0, 1, 2].map(|corner| {
+                *vertices
+                    .entry((face.material_block_index, corners[corner]))
+                    .or_insert_with(|| {
+                        mesh.positions.push(positions[corner]);
+
+                        mesh.positions.len() as u32 - 1
+                    })
+            });
+
+            mesh.indices.push(triangle);
+            mesh.materials.push(CollisionMaterial {
+                material_hash: self.model_parts[face.material_block_index as usize].material_hash,
+                material_block_index: face.material_block_index,
+                face_index: face.face_index,
+            });
+        }
+
+        mesh
+    }
+}
+
+/// Builds the collision mesh of every `Collision` chunk of `bsp`, resolved against the model
+/// parts of the `ModelGroup` chunk before it.
+pub fn collision_meshes(bsp: &Bsp) -> Vec<CollisionMesh> {
+    CollisionWorld::all(bsp)
+        .iter()
+        .map(CollisionWorld::triangle_mesh)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Collision, Index, Leaf, ModelPart, QuantizedPlane, Vertex};
+
+    #[test]
+    fn resolves_faces_against_model_parts() {
+        let vertex = |x, y| Vertex {
+            vertex: Some(Vector3::new(x, y, 0.0)),
+            ..Default::default()
+        };
+        let model_part = ModelPart {
+            material_hash: 0xAB,
+            vertices: vec![
+                vertex(0.0, 0.0),
+                vertex(1.0, 0.0),
+                vertex(0.0, 1.0),
+                vertex(1.0, 1.0),
+            ],
+            indices: vec![
+                Index {
+                    index0: 0,
+                    index1: 1,
+                    index2: 2,
+                },
+                Index {
+                    index0: 2,
+                    index1: 1,
+                    index2: 3,
+                },
+            ],
+            ..Default::default()
+        };
+        let face = |material_block_index, face_index| Leaf {
+            plane: QuantizedPlane {
+                a: 128,
+                b: 128,
+                c: 255,
+                flags: 0,
+                d: 0.0,
+            },
+            material_block_index,
+            face_index,
+        };
+        let collision = Collision {
+            faces: vec![face(0, 0), face(0, 1), face(0, 1), face(1, 0), face(0, 9)],
+            leaves: vec![0],
+            branches: Vec::new(),
+        };
+
+        let mesh = CollisionWorld::new(&collision, vec![&model_part]).triangle_mesh();
+
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [2, 1, 3]]);
+        assert_eq!(mesh.materials[1].material_hash, 0xAB);
+        assert_eq!(mesh.materials[1].face_index, 1);
+    }
+}
@@ -0,0 +1,612 @@
+//! Queries on the collision BSP of a level.
+//!
+//! The tree is assumed to be laid out as follows: nodes are numbered with the branches first and
+//! the leaves after them, node 0 being the root. A branch's `index` is the node in front of its
+//! plane and `index + 1` the node behind it. `leaves[n]` is the first face of leaf `n`, whose
+//! faces run up to the first face of the next leaf.
+//!
+//! Each face is a triangle of the world mesh: `face_index` indexes the triangles of the model
+//! part at `material_block_index`, and `plane` is the plane of that triangle, facing out of the
+//! solid it bounds. Queries test the triangles themselves, so they need the model parts the
+//! tree refers to, which `CollisionWorld` pairs with it.
+
+use crate::{
+    take_model_parts, BoundingBox, Bsp, Chunk, Collision, Leaf, ModelPart, QuantizedPlane, Vector3,
+};
+
+/// Keeps hits from starting behind the surface they were reported on because of rounding.
+const EPSILON: f32 = 1e-4;
+
+/// Solidity is tested by casting a ray from the point in this direction.
+const SOLIDITY_RAY: Vector3 = Vector3 {
+    x: 0.0,
+    y: 1.0,
+    z: 0.0,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollisionHit {
+    /// Where the segment, or the center of the swept sphere, stops.
+    pub point: Vector3,
+    pub normal: Vector3,
+    /// How far along the segment the hit is, from 0 at the start to 1 at the end.
+    pub fraction: f32,
+    pub face_index: u16,
+    pub material_block_index: u16,
+}
+
+/// A collision BSP together with the material blocks of the mesh it belongs to.
+#[derive(Clone, Debug)]
+pub struct CollisionWorld<'a> {
+    pub collision: &'a Collision,
+    pub model_parts: Vec<&'a ModelPart>,
+    bounds: Option<BoundingBox>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Node {
+    Branch(usize),
+    Leaf(usize),
+}
+
+impl QuantizedPlane {
+    /// The unit normal, whose components are stored as bytes mapping `[0, 255]` to `[-1, 1]`.
+    pub fn normal(&self) -> Vector3 {
+        let component = |value: u8| value as f32 / 127.5 - 1.0;
+
+        Vector3::new(component(self.a), component(self.b), component(self.c)).normalized()
+    }
+
+    /// The signed distance of `point` to the plane, positive in front of it.
+    pub fn distance(&self, point: &Vector3) -> f32 {
+        self.normal().dot(point) - self.d
+    }
+}
+
+impl Collision {
+    /// The faces of leaf `leaf`.
+    pub fn leaf_faces(&self, leaf: usize) -> &[Leaf] {
+        let start = self.leaves.get(leaf).map_or(0, |start| *start as usize);
+        let end = self
+            .leaves
+            .get(leaf + 1)
+            .map_or(self.faces.len(), |end| *end as usize);
+
+        self.faces
+            .get(start.min(end)..end.min(self.faces.len()))
+            .unwrap_or_default()
+    }
+
+    fn root(&self) -> Option<Node> {
+        self.node(0)
+    }
+
+    fn node(&self, index: usize) -> Option<Node> {
+        if index < self.branches.len() {
+            Some(Node::Branch(index))
+        } else if index - self.branches.len() < self.leaves.len() {
+            Some(Node::Leaf(index - self.branches.len()))
+        } else {
+            None
+        }
+    }
+
+    /// Gathers the leaves the segment, grown by `radius`, passes through, sorted and without
+    /// duplicates. Each branch is visited at most once.
+    fn collect_leaves(&self, start: &Vector3, end: &Vector3, radius: f32) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        let mut visited = vec![false; self.branches.len()];
+        let mut stack = vec![self.root()];
+
+        while let Some(node) = stack.pop() {
+            let branch = match node {
+                Some(Node::Branch(branch)) => {
+                    if std::mem::replace(&mut visited[branch], true) {
+                        continue;
+                    }
+
+                    &self.branches[branch]
+                }
+                Some(Node::Leaf(leaf)) => {
+                    leaves.push(leaf);
+                    continue;
+                }
+                None => continue,
+            };
+            let start_distance = branch.plane.distance(start);
+            let end_distance = branch.plane.distance(end);
+            let front = branch.index as usize;
+
+            if start_distance >= -radius || end_distance >= -radius {
+                stack.push(self.node(front));
+            }
+
+            if start_distance < radius || end_distance < radius {
+                stack.push(self.node(front + 1));
+            }
+        }
+
+        leaves.sort_unstable();
+        leaves.dedup();
+
+        leaves
+    }
+}
+
+impl<'a> CollisionWorld<'a> {
+    /// Pairs `collision` with `model_parts`, the material blocks of the mesh it belongs to.
+    pub fn new(collision: &'a Collision, model_parts: Vec<&'a ModelPart>) -> Self {
+        let bounds = model_parts
+            .iter()
+            .flat_map(|model_part| &model_part.vertices)
+            .filter_map(|vertex| vertex.vertex)
+            .fold(None, |bounds: Option<BoundingBox>, position| {
+                Some(match bounds {
+                    Some(bounds) => BoundingBox {
+                        infimum: Vector3::new(
+                            bounds.infimum.x.min(position.x),
+                            bounds.infimum.y.min(position.y),
+                            bounds.infimum.z.min(position.z),
+                        ),
+                        supremum: Vector3::new(
+                            bounds.supremum.x.max(position.x),
+                            bounds.supremum.y.max(position.y),
+                            bounds.supremum.z.max(position.z),
+                        ),
+                    },
+                    None => BoundingBox {
+                        infimum: position,
+                        supremum: position,
+                    },
+                })
+            });
+
+        Self {
+            collision,
+            model_parts,
+            bounds,
+        }
+    }
+
+    /// Every `Collision` chunk of `bsp`, paired with the model parts of the `ModelGroup` chunk
+    /// before it.
+    pub fn all(bsp: &'a Bsp) -> Vec<Self> {
+        let mut worlds = Vec::new();
+        let mut model_parts = Vec::new();
+        let mut chunks = bsp.chunks.iter().peekable();
+
+        while let Some(chunk) = chunks.next() {
+            match chunk {
+                Chunk::ModelGroup(mesh) => model_parts = take_model_parts(mesh, &mut chunks),
+                Chunk::Collision(collision) => {
+                    worlds.push(Self::new(collision, model_parts.clone()))
+                }
+                _ => (),
+            }
+        }
+
+        worlds
+    }
+
+    /// The corners of the triangle `face` refers to, if it resolves to one.
+    pub fn triangle(&self, face: &Leaf) -> Option<[Vector3; 3]> {
+        self.resolve(face).map(|(_, positions)| positions)
+    }
+
+    /// The vertex indices in the model part of the triangle `face` refers to, and their
+    /// positions.
+    pub(crate) fn resolve(&self, face: &Leaf) -> Option<([u32; 3], [Vector3; 3])> {
+        let model_part = self.model_parts.get(face.material_block_index as usize)?;
+        let index = model_part.indices.get(face.face_index as usize)?;
+        let corners = [index.index0, index.index1, index.index2];
+        let position = |corner: u32| {
+            model_part
+                .vertices
+                .get(corner as usize)
+                .and_then(|vertex| vertex.vertex)
+        };
+
+        Some((
+            corners,
+            [
+                position(corners[0])?,
+                position(corners[1])?,
+                position(corners[2])?,
+            ],
+        ))
+    }
+
+    /// Whether `point` lies inside a solid, which is the case when the first triangle above it
+    /// faces away from it.
+    pub fn is_solid(&self, point: &Vector3) -> bool {
+        let Some(bounds) = &self.bounds else {
+            return false;
+        };
+        let reach =
+            (bounds.supremum - bounds.infimum).length() + (*point - bounds.center()).length() + 1.0;
+        let end = *point + SOLIDITY_RAY * reach;
+
+        self.first_hit(point, &end, 0.0, true)
+            .is_some_and(|(hit, face)| {
+                hit.fraction > 0.0 && face.plane.normal().dot(&SOLIDITY_RAY) > 0.0
+            })
+    }
+
+    /// Finds the first triangle the segment from `start` to `end` crosses, from either side.
+    pub fn cast_segment(&self, start: &Vector3, end: &Vector3) -> Option<CollisionHit> {
+        self.sweep_sphere(start, end, 0.0)
+    }
+
+    /// Casts a ray of at most `maximum_distance` along `direction`.
+    pub fn cast_ray(
+        &self,
+        origin: &Vector3,
+        direction: &Vector3,
+        maximum_distance: f32,
+    ) -> Option<CollisionHit> {
+        self.cast_segment(
+            origin,
+            &(*origin + direction.normalized() * maximum_distance),
+        )
+    }
+
+    /// Moves a sphere of `radius` from `start` to `end` and returns where it first touches a
+    /// triangle, on its face, along an edge or at a corner. Triangles the sphere starts
+    /// touching are not reported, so that a sphere resting on the ground can move off it.
+    pub fn sweep_sphere(
+        &self,
+        start: &Vector3,
+        end: &Vector3,
+        radius: f32,
+    ) -> Option<CollisionHit> {
+        self.first_hit(start, end, radius, false)
+            .map(|(hit, _)| hit)
+    }
+
+    fn first_hit(
+        &self,
+        start: &Vector3,
+        end: &Vector3,
+        radius: f32,
+        keep_exact_fraction: bool,
+    ) -> Option<(CollisionHit, &'a Leaf)> {
+        let collision = self.collision;
+        let delta = *end - *start;
+        let backoff = EPSILON / delta.length().max(EPSILON);
+
+        collision
+            .collect_leaves(start, end, radius)
+            .into_iter()
+            .flat_map(|leaf| collision.leaf_faces(leaf))
+            .filter_map(|face| {
+                let triangle = self.triangle(face)?;
+                let (fraction, normal) = sweep_triangle(start, &delta, radius, &triangle)?;
+                let fraction = if keep_exact_fraction {
+                    fraction
+                } else {
+                    (fraction - backoff).max(0.0)
+                };
+
+                Some((
+                    CollisionHit {
+                        point: start.lerp(end, fraction),
+                        normal,
+                        fraction,
+                        face_index: face.face_index,
+                        material_block_index: face.material_block_index,
+                    },
+                    face,
+                ))
+            })
+            .min_by(|(a, _), (b, _)| a.fraction.total_cmp(&b.fraction))
+    }
+}
+
+/// Where a sphere of `radius` moving from `start` by `delta` first touches `triangle`, as a
+/// fraction of `delta`, and the normal of the contact facing the sphere. A radius of 0 casts a
+/// segment, which only the face of the triangle can stop.
+fn sweep_triangle(
+    start: &Vector3,
+    delta: &Vector3,
+    radius: f32,
+    triangle: &[Vector3; 3],
+) -> Option<(f32, Vector3)> {
+    let [a, b, c] = *triangle;
+    let winding_normal = (b - a).cross(&(c - a)).normalized();
+
+    if winding_normal.length() == 0.0 {
+        return None;
+    }
+
+    let start_distance = winding_normal.dot(&(*start - a));
+    let normal = if start_distance < 0.0 {
+        -winding_normal
+    } else {
+        winding_normal
+    };
+    let start_distance = start_distance.abs();
+    let speed = normal.dot(delta);
+
+    // The face, reached when the sphere touches the plane inside the triangle.
+    if start_distance >= radius && speed < 0.0 {
+        let fraction = (start_distance - radius) / -speed;
+        let contact = *start + *delta * fraction - normal * radius;
+
+        if fraction <= 1.0 && contains(triangle, &winding_normal, &contact) {
+            return Some((fraction, normal));
+        }
+    }
+
+    if radius == 0.0 {
+        return None;
+    }
+
+    let edges = [(a, b), (b, c), (c, a)];
+    let edge_hits = edges
+        .iter()
+        .filter_map(|(p, q)| sweep_edge(start, delta, radius, p, q));
+    let corner_hits = triangle
+        .iter()
+        .filter_map(|corner| sweep_corner(start, delta, radius, corner));
+
+    edge_hits
+        .chain(corner_hits)
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+}
+
+/// Where a sphere first touches the edge from `p` to `q`, treated as a cylinder of `radius`.
+fn sweep_edge(
+    start: &Vector3,
+    delta: &Vector3,
+    radius: f32,
+    p: &Vector3,
+    q: &Vector3,
+) -> Option<(f32, Vector3)> {
+    let edge = *q - *p;
+    let offset = *start - *p;
+    let edge_squared = edge.dot(&edge);
+    let edge_delta = edge.dot(delta);
+    let edge_offset = edge.dot(&offset);
+    let a = edge_squared * delta.dot(delta) - edge_delta * edge_delta;
+    let b = edge_squared * offset.dot(delta) - edge_offset * edge_delta;
+    let c = edge_squared * (offset.dot(&offset) - radius * radius) - edge_offset * edge_offset;
+
+    // Moving along the edge, or starting within the cylinder.
+    if a <= f32::EPSILON || c <= 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - a * c;
+
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let fraction = (-b - discriminant.sqrt()) / a;
+    let along = (edge_offset + edge_delta * fraction) / edge_squared;
+
+    if !(0.0..=1.0).contains(&fraction) || !(0.0..=1.0).contains(&along) {
+        return None;
+    }
+
+    let center = *start + *delta * fraction;
+
+    Some((fraction, (center - (*p + edge * along)).normalized()))
+}
+
+/// Where a sphere first touches `corner`.
+fn sweep_corner(
+    start: &Vector3,
+    delta: &Vector3,
+    radius: f32,
+    corner: &Vector3,
+) -> Option<(f32, Vector3)> {
+    let offset = *start - *corner;
+    let a = delta.dot(delta);
+    let b = offset.dot(delta);
+    let c = offset.dot(&offset) - radius * radius;
+
+    // Moving away, or starting within the sphere around the corner.
+    if a == 0.0 || b >= 0.0 || c <= 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - a * c;
+
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let fraction = (-b - discriminant.sqrt()) / a;
+
+    (fraction <= 1.0).then(|| {
+        let center = *start + *delta * fraction;
+
+        (fraction, (center - *corner).normalized())
+    })
+}
+
+/// Whether `point`, in the plane of `triangle`, is inside it or on its border. `normal` follows
+/// the winding of the triangle.
+fn contains(triangle: &[Vector3; 3], normal: &Vector3, point: &Vector3) -> bool {
+    (0..3).all(|corner| {
+        let from = triangle[corner];
+        let to = triangle[(corner + 1) % 3];
+
+        (to - from).cross(&(*point - from)).dot(normal) >= -EPSILON
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Branch, Index, Vertex};
+
+    fn plane(normal: (u8, u8, u8), d: f32) -> QuantizedPlane {
+        QuantizedPlane {
+            a: normal.0,
+            b: normal.1,
+            c: normal.2,
+            flags: 0,
+            d,
+        }
+    }
+
+    /// A cube spanning [-1, 1]³, two triangles per side, wound counterclockwise seen from
+    /// outside.
+    fn cube() -> ModelPart {
+        let vertices = (0..8)
+            .map(|corner: u32| {
+                let coordinate = |bit: u32| if corner & (1 << bit) == 0 { -1.0 } else { 1.0 };
+
+                Vertex {
+                    vertex: Some(Vector3::new(coordinate(0), coordinate(1), coordinate(2))),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let sides: [[u32; 4]; 6] = [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ];
+        let indices = sides
+            .iter()
+            .flat_map(|[a, b, c, d]| [[*a, *b, *c], [*a, *c, *d]])
+            .map(|[index0, index1, index2]| Index {
+                index0,
+                index1,
+                index2,
+            })
+            .collect();
+
+        ModelPart {
+            vertices,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    /// The cube behind a branch at x = 5, in a single leaf; nothing is in front of the branch.
+    fn collision() -> Collision {
+        let normals = [
+            (0, 128, 128),
+            (255, 128, 128),
+            (128, 0, 128),
+            (128, 255, 128),
+            (128, 128, 0),
+            (128, 128, 255),
+        ];
+
+        Collision {
+            faces: (0..12)
+                .map(|face_index| Leaf {
+                    plane: plane(normals[face_index as usize / 2], 1.0),
+                    material_block_index: 0,
+                    face_index,
+                })
+                .collect(),
+            leaves: vec![0, 0],
+            branches: vec![Branch {
+                plane: plane((255, 128, 128), 5.0),
+                index: 1,
+            }],
+        }
+    }
+
+    #[test]
+    fn finds_solid_points() {
+        let collision = collision();
+        let cube = cube();
+        let world = CollisionWorld::new(&collision, vec![&cube]);
+
+        assert!(world.is_solid(&Vector3::new(0.5, 0.0, -0.5)));
+        assert!(!world.is_solid(&Vector3::new(1.5, 0.0, 0.0)));
+        assert!(!world.is_solid(&Vector3::new(0.0, -3.0, 0.0)));
+    }
+
+    #[test]
+    fn casts_segments_and_spheres() {
+        let collision = collision();
+        let cube = cube();
+        let world = CollisionWorld::new(&collision, vec![&cube]);
+        let start = Vector3::new(-3.0, 0.2, 0.1);
+        let end = Vector3::new(3.0, 0.2, 0.1);
+
+        let hit = world.cast_segment(&start, &end).unwrap();
+
+        assert!(hit.face_index < 2);
+        assert_eq!(hit.material_block_index, 0);
+        assert!((hit.point.x + 1.0).abs() < 1e-2);
+        assert!(hit.normal.x < -0.99);
+
+        let hit = world.sweep_sphere(&start, &end, 0.5).unwrap();
+
+        assert!((hit.point.x + 1.5).abs() < 1e-2);
+
+        // Passing 0.3 above the top edge, the sphere touches it 0.4 before the side.
+        let hit = world
+            .sweep_sphere(
+                &Vector3::new(-3.0, 1.3, 0.0),
+                &Vector3::new(3.0, 1.3, 0.0),
+                0.5,
+            )
+            .unwrap();
+
+        assert!((hit.point.x + 1.4).abs() < 1e-2);
+        assert!(world
+            .cast_segment(&Vector3::new(-3.0, 2.0, 0.0), &Vector3::new(3.0, 2.0, 0.0))
+            .is_none());
+    }
+
+    mod levels {
+        use crate::{bsp::decode_level, Chunk};
+        use test_case::test_case;
+
+        /// Checks decoded trees against the layout in the module documentation: every child
+        /// index names a node, leaf face ranges are ordered and within `faces`, and plane normals
+        /// decode to unit length before being normalized.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_layout(asset: &str) {
+            let bsp = decode_level(asset);
+
+            for chunk in &bsp.chunks {
+                let Chunk::Collision(collision) = chunk else {
+                    continue;
+                };
+                let node_count = collision.branches.len() + collision.leaves.len();
+
+                for branch in &collision.branches {
+                    assert!((branch.index as usize) + 1 < node_count);
+                }
+
+                assert!(collision.leaves.windows(2).all(|pair| pair[0] <= pair[1]));
+                assert!(collision
+                    .leaves
+                    .iter()
+                    .all(|start| *start as usize <= collision.faces.len()));
+
+                for plane in collision
+                    .faces
+                    .iter()
+                    .map(|face| &face.plane)
+                    .chain(collision.branches.iter().map(|branch| &branch.plane))
+                {
+                    let component = |value: u8| value as f32 / 127.5 - 1.0;
+                    let length = (component(plane.a).powi(2)
+                        + component(plane.b).powi(2)
+                        + component(plane.c).powi(2))
+                    .sqrt();
+
+                    assert!((length - 1.0).abs() < 0.02, "{plane:?}");
+                }
+            }
+        }
+    }
+}
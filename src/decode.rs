@@ -11,6 +11,7 @@ pub struct NullTerminated<T>(PhantomData<T>);
 #[derive(Debug)]
 pub enum DecodeError {
     ReadTooMuchData { expected: usize, actual: usize },
+    CountMismatch { expected: usize, actual: usize },
     ConversionFailure,
     IO(io::Error),
 }
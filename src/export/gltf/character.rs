@@ -0,0 +1,670 @@
+use super::{column_major, GltfBuilder, ARRAY_BUFFER};
+use crate::animation::{aligned_rotations, catmull_rom_tangent, locate};
+use crate::{
+    frame_hierarchy, mesh_parts, AnimationClip, AnimationKeys, AnimationLibrary, Bsp, Chunk, Clump,
+    ExportError, Frame, Interpolation, Matrix, MeshPart, ModelPart, ShapeTrack, TrackTargets,
+    Vector3, Vertex, WrapMode,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+};
+
+/// Writes a ghost model as binary glTF, with a skin built from its `SkinObj` bones and one
+/// animation per clip of `animations`.
+///
+/// Rotation and translation tracks are bound to joints through `target_hash`, which is matched
+/// against bone ids, frame ids and hashed frame names. Every key of a `Shape` track, compressed
+/// keys rebuilt by `AnimationKey::shape_frames`, becomes a morph target of the part it animates
+/// through `MeshPart::is_animated_by`, and the shape tracks of a part share one weights channel.
+pub fn export_character_glb(
+    model: &Bsp,
+    animations: Option<&Bsp>,
+    writer: &mut impl Write,
+) -> Result<(), ExportError> {
+    let mut builder = GltfBuilder::new(model);
+    let mut frames: Vec<&Frame> = Vec::new();
+    let mut nodes: Vec<usize> = Vec::new();
+
+    for skeleton_frame in frame_hierarchy(&model.chunks) {
+        let frame = skeleton_frame.frame;
+        let parent = skeleton_frame.parent.map(|parent| nodes[parent]);
+        let matrix = if parent.is_some() {
+            &frame.local_transform_matrix
+        } else {
+            &frame.global_transform_matrix
+        };
+        let mut node = trs_json(matrix);
+
+        if !frame.name.is_empty() {
+            node["name"] = json!(frame.name);
+        }
+
+        frames.push(frame);
+        nodes.push(builder.push_node(node, parent));
+    }
+
+    let clump = model.chunks.iter().find_map(|chunk| match chunk {
+        Chunk::SkinObj(skin) => Some(skin),
+        _ => None,
+    });
+    let mesh_parts = mesh_parts(&model.chunks);
+
+    let mut targets = TrackTargets::new(&frames, clump);
+    let skin = clump.map(|clump| push_skin(&mut builder, clump, &frames, &mut nodes, &mut targets));
+
+    let clips = match animations {
+        Some(animations) => AnimationLibrary::new(animations)?.clips,
+        None => Vec::new(),
+    };
+    let mut shape_targets = HashMap::new();
+    let mut part_nodes = HashMap::new();
+
+    for (index, mesh_part) in mesh_parts.iter().enumerate() {
+        let model_part = mesh_part.model_part;
+        let name = format!("part_{}", index);
+        let mesh = match builder.mesh(Some(&name), &[model_part])? {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        let skinned = skin.is_some()
+            && model_part
+                .vertices
+                .iter()
+                .all(|vertex| vertex.indices.is_some());
+
+        if skinned {
+            push_skin_attributes(&mut builder, mesh, model_part);
+        }
+
+        let morph_target_count = push_morph_targets(
+            &mut builder,
+            mesh,
+            index,
+            mesh_part,
+            &clips,
+            &mut shape_targets,
+        )?;
+
+        let mut node = json!({ "name": name, "mesh": mesh });
+
+        if skinned {
+            node["skin"] = json!(skin);
+        }
+
+        part_nodes.insert(index, (builder.push_node(node, None), morph_target_count));
+    }
+
+    for (clip_index, clip) in clips.iter().enumerate() {
+        push_animation(
+            &mut builder,
+            clip_index,
+            clip,
+            &targets,
+            &nodes,
+            &part_nodes,
+            &shape_targets,
+        )?;
+    }
+
+    builder.write_glb(writer)
+}
+
+/// Adds a skin joining the node of each bone's frame. Bones without a frame get a node of their
+/// own at their bind pose, which is appended to `nodes` so tracks can still target it.
+fn push_skin(
+    builder: &mut GltfBuilder,
+    clump: &Clump,
+    frames: &[&Frame],
+    nodes: &mut Vec<usize>,
+    targets: &mut TrackTargets,
+) -> usize {
+    let mut joints = Vec::with_capacity(clump.bone_count.len());
+    let mut inverse_bind_matrices = Vec::with_capacity(clump.bone_count.len() * 16);
+
+    for (bone_index, bone) in clump.bone_count.iter().enumerate() {
+        let joint = match frames
+            .iter()
+            .position(|frame| frame.bone_index == bone_index as i32)
+        {
+            Some(index) => nodes[index],
+            None => {
+                let bind_pose = bone.inverted_base_pose.inverse().unwrap_or_default();
+                let mut node = trs_json(&bind_pose);
+
+                node["name"] = json!(format!("bone_{}", bone_index));
+
+                let node = builder.push_node(node, None);
+
+                targets.insert(bone.bone_id, nodes.len());
+                nodes.push(node);
+                node
+            }
+        };
+
+        joints.push(joint);
+        inverse_bind_matrices.extend_from_slice(&column_major(&bone.inverted_base_pose));
+    }
+
+    let inverse_bind_matrices = builder.push_floats(&inverse_bind_matrices, 16, None, false);
+
+    builder.skins.push(json!({
+        "joints": joints,
+        "inverseBindMatrices": inverse_bind_matrices,
+    }));
+
+    builder.skins.len() - 1
+}
+
+fn push_skin_attributes(builder: &mut GltfBuilder, mesh: usize, model_part: &ModelPart) {
+    let mut joints = Vec::with_capacity(model_part.vertices.len() * 4);
+    let mut weights = Vec::with_capacity(model_part.vertices.len() * 4);
+
+    for vertex in &model_part.vertices {
+        let (index0, index1) = vertex.indices.unwrap();
+        let weight = vertex.weight.unwrap_or(1.0).clamp(0.0, 1.0);
+
+        if index0 == index1 {
+            joints.extend_from_slice(&[index0, 0, 0, 0]);
+            weights.extend_from_slice(&[1.0, 0.0, 0.0, 0.0]);
+        } else {
+            joints.extend_from_slice(&[index0, index1, 0, 0]);
+            weights.extend_from_slice(&[weight, 1.0 - weight, 0.0, 0.0]);
+        }
+    }
+
+    let joints = builder.push_shorts(&joints, 4);
+    let weights = builder.push_floats(&weights, 4, Some(ARRAY_BUFFER), false);
+    let attributes = &mut builder.meshes[mesh]["primitives"][0]["attributes"];
+
+    attributes["JOINTS_0"] = json!(joints);
+    attributes["WEIGHTS_0"] = json!(weights);
+}
+
+/// Adds one morph target per key of every shape track that animates the part, recording the
+/// part and its first target under the clip and key indices, and returns the number of targets.
+///
+/// Tracks are bound to the part's vertices through `ShapeTrack`, so tracks that cannot be decoded
+/// or bound are reported rather than exported with made up deltas.
+fn push_morph_targets(
+    builder: &mut GltfBuilder,
+    mesh: usize,
+    part_index: usize,
+    mesh_part: &MeshPart,
+    clips: &[AnimationClip],
+    shape_targets: &mut HashMap<(usize, usize), (usize, usize)>,
+) -> Result<usize, ExportError> {
+    let model_part = mesh_part.model_part;
+    let mut morph_targets = Vec::new();
+    let has_normals = model_part
+        .vertices
+        .iter()
+        .all(|vertex| vertex.normal.is_some());
+
+    for (clip_index, clip) in clips.iter().enumerate() {
+        for (key_index, key) in clip.keys.iter().enumerate() {
+            if !mesh_part.is_animated_by(key) {
+                continue;
+            }
+
+            let track = ShapeTrack::new(key, model_part)?;
+
+            shape_targets.insert((clip_index, key_index), (part_index, morph_targets.len()));
+
+            for frame in &track.frames {
+                let positions = deltas(
+                    model_part,
+                    &track.vertex_indices,
+                    &frame.positions,
+                    |vertex| vertex.vertex,
+                );
+                let mut target = Map::new();
+
+                target.insert(
+                    "POSITION".into(),
+                    json!(builder.push_floats(&positions, 3, Some(ARRAY_BUFFER), true)),
+                );
+
+                if has_normals && !frame.normals.is_empty() {
+                    let normals = deltas(
+                        model_part,
+                        &track.vertex_indices,
+                        &frame.normals,
+                        |vertex| vertex.normal,
+                    );
+
+                    target.insert(
+                        "NORMAL".into(),
+                        json!(builder.push_floats(&normals, 3, Some(ARRAY_BUFFER), false)),
+                    );
+                }
+
+                morph_targets.push(Value::Object(target));
+            }
+        }
+    }
+
+    let morph_target_count = morph_targets.len();
+
+    if morph_target_count > 0 {
+        builder.meshes[mesh]["primitives"][0]["targets"] = Value::Array(morph_targets);
+    }
+
+    Ok(morph_target_count)
+}
+
+/// Flattens the difference between the animated elements and the vertex data they replace, which
+/// is zero for the vertices the track leaves alone.
+fn deltas(
+    model_part: &ModelPart,
+    vertex_indices: &[usize],
+    elements: &[Vector3],
+    base: impl Fn(&Vertex) -> Option<Vector3>,
+) -> Vec<f32> {
+    let mut deltas = vec![Vector3::default(); model_part.vertices.len()];
+
+    for (vertex_index, element) in vertex_indices.iter().zip(elements) {
+        if let Some(base) = base(&model_part.vertices[*vertex_index]) {
+            deltas[*vertex_index] = *element - base;
+        }
+    }
+
+    deltas
+        .iter()
+        .flat_map(|delta| [delta.x, delta.y, delta.z])
+        .collect()
+}
+
+/// Adds the clip as an animation. Rotation and translation tracks drive the node of their target,
+/// and the shape tracks of a part are merged into one weights channel of its node.
+fn push_animation(
+    builder: &mut GltfBuilder,
+    clip_index: usize,
+    clip: &AnimationClip,
+    targets: &TrackTargets,
+    nodes: &[usize],
+    part_nodes: &HashMap<usize, (usize, usize)>,
+    shape_targets: &HashMap<(usize, usize), (usize, usize)>,
+) -> Result<(), ExportError> {
+    let mut samplers = Vec::new();
+    let mut channels = Vec::new();
+    let mut animated = HashSet::new();
+    let mut weight_tracks: BTreeMap<usize, Vec<(Vec<f32>, usize)>> = BTreeMap::new();
+
+    for (key_index, key) in clip.keys.iter().enumerate() {
+        let times = key.key_times();
+
+        let (node, path, values, components) = match &key.keys {
+            AnimationKeys::Rotations(rotations) => {
+                let node = match targets.get(key.target_hash) {
+                    Some(index) => nodes[index],
+                    None => continue,
+                };
+                let values =
+                    aligned_rotations(rotations.iter().map(|rotation| rotation.dequantize()))
+                        .iter()
+                        .flat_map(|rotation| [rotation.x, rotation.y, rotation.z, rotation.w])
+                        .collect::<Vec<_>>();
+
+                (node, "rotation", values, 4)
+            }
+            AnimationKeys::Translations(translations) => {
+                let node = match targets.get(key.target_hash) {
+                    Some(index) => nodes[index],
+                    None => continue,
+                };
+                let values = translations
+                    .iter()
+                    .flat_map(|translation| [translation.x, translation.y, translation.z])
+                    .collect::<Vec<_>>();
+
+                (node, "translation", values, 3)
+            }
+            AnimationKeys::Shapes(shapes) => {
+                if let Some((part_index, first_target)) =
+                    shape_targets.get(&(clip_index, key_index))
+                {
+                    let mut times = times;
+
+                    times.truncate(shapes.len());
+                    weight_tracks
+                        .entry(*part_index)
+                        .or_default()
+                        .push((times, *first_target));
+                }
+
+                continue;
+            }
+            _ => continue,
+        };
+
+        if times.is_empty() {
+            continue;
+        }
+
+        if !animated.insert((node, path)) {
+            return Err(ExportError::DuplicateTrack {
+                clip: clip.clip.name.clone(),
+                target_hash: key.target_hash,
+            });
+        }
+
+        let (values, interpolation) = match key.interpolation_type {
+            Interpolation::CubicSpline => {
+                (cubic_spline(&times, &values, components), "CUBICSPLINE")
+            }
+            Interpolation::Linear => (values, "LINEAR"),
+        };
+
+        push_channel(
+            builder,
+            &mut samplers,
+            &mut channels,
+            (node, path),
+            (&times, &values, components),
+            interpolation,
+        );
+    }
+
+    for (part_index, tracks) in &weight_tracks {
+        let Some((node, target_count)) = part_nodes.get(part_index) else {
+            continue;
+        };
+        let (times, values) = merge_weights(tracks, *target_count);
+
+        if !times.is_empty() {
+            push_channel(
+                builder,
+                &mut samplers,
+                &mut channels,
+                (*node, "weights"),
+                (&times, &values, 1),
+                "LINEAR",
+            );
+        }
+    }
+
+    if !channels.is_empty() {
+        builder.animations.push(json!({
+            "name": clip.clip.name,
+            "samplers": samplers,
+            "channels": channels,
+        }));
+    }
+
+    Ok(())
+}
+
+fn push_channel(
+    builder: &mut GltfBuilder,
+    samplers: &mut Vec<Value>,
+    channels: &mut Vec<Value>,
+    (node, path): (usize, &str),
+    (times, values, components): (&[f32], &[f32], usize),
+    interpolation: &str,
+) {
+    let input = builder.push_floats(times, 1, None, true);
+    let output = builder.push_floats(values, components, None, false);
+
+    samplers.push(json!({
+        "input": input,
+        "output": output,
+        "interpolation": interpolation,
+    }));
+    channels.push(json!({
+        "sampler": samplers.len() - 1,
+        "target": { "node": node, "path": path },
+    }));
+}
+
+/// Samples every shape track of a part at the key times of all of them. A track blends linearly
+/// between the targets of its two surrounding keys, and the weights of the tracks are summed.
+fn merge_weights(tracks: &[(Vec<f32>, usize)], target_count: usize) -> (Vec<f32>, Vec<f32>) {
+    let mut times = tracks
+        .iter()
+        .flat_map(|(times, _)| times.iter().copied())
+        .collect::<Vec<_>>();
+
+    times.sort_by(f32::total_cmp);
+    times.dedup();
+
+    let mut values = Vec::with_capacity(times.len() * target_count);
+
+    for time in &times {
+        let mut weights = vec![0.0; target_count];
+
+        for (track_times, first_target) in tracks {
+            if let Some((index, t)) = locate(track_times, *time, WrapMode::Clamp) {
+                let next = (index + 1).min(track_times.len() - 1);
+
+                weights[first_target + index] += 1.0 - t;
+                weights[first_target + next] += t;
+            }
+        }
+
+        values.extend(weights);
+    }
+
+    (times, values)
+}
+
+/// Expands values into glTF's in-tangent, value, out-tangent triplets using Catmull-Rom tangents.
+fn cubic_spline(times: &[f32], values: &[f32], components: usize) -> Vec<f32> {
+    let mut output = Vec::with_capacity(values.len() * 3);
+
+    for index in 0..times.len() {
+        let tangent = catmull_rom_tangent(times, values, components, index);
+
+        output.extend_from_slice(&tangent);
+        output.extend_from_slice(&values[index * components..(index + 1) * components]);
+        output.extend_from_slice(&tangent);
+    }
+
+    output
+}
+
+fn trs_json(matrix: &Matrix) -> Value {
+    let (scale, rotation, translation) = matrix.decompose();
+
+    json!({
+        "translation": [translation.x, translation.y, translation.z],
+        "rotation": [rotation.x, rotation.y, rotation.z, rotation.w],
+        "scale": [scale.x, scale.y, scale.z],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::gltf::glb_json;
+    use crate::{
+        AnimationDictionary, AnimationKey, Bone, Clips, FrameChild, KeyFrameAnimatedVertices,
+        KeyFrameNormals, Shape, Vector4,
+    };
+
+    fn frame(bone_index: i32, id: u32) -> Chunk {
+        let mut matrix = Matrix::identity();
+
+        matrix.position = Vector4::new(1.0, 0.0, 0.0, 1.0);
+
+        Chunk::BoneObj(Frame {
+            local_transform_matrix: matrix.clone(),
+            global_transform_matrix: matrix,
+            bone_index,
+            flags: 0,
+            id,
+            name: String::new(),
+        })
+    }
+
+    fn key(target_hash: u32, keys: AnimationKeys) -> Chunk {
+        Chunk::AnimationKey(AnimationKey {
+            type_: 0,
+            target_hash,
+            time_step: 1.0,
+            key_count: 2,
+            material_block_index: 0,
+            bounding_box_maximum: None,
+            interpolation_type: Interpolation::Linear,
+            times: None,
+            keys,
+            adaptive_differential_pulse_code_modulation: None,
+        })
+    }
+
+    fn key_frame(x: f32) -> Shape {
+        Shape::KeyFrame {
+            animated_vertices: KeyFrameAnimatedVertices {
+                elements: vec![Vector3::new(x, 0.0, 0.0)],
+            },
+            normals: KeyFrameNormals {
+                elements: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn exports_skin_and_animation() {
+        let bone = |bone_id| Bone {
+            bone_id,
+            inverted_base_pose: Matrix::identity(),
+        };
+        let model = Bsp {
+            chunks: vec![
+                Chunk::LevelObj(FrameChild { stream_depth: 0 }),
+                frame(0, 100),
+                Chunk::LevelObj(FrameChild { stream_depth: 1 }),
+                frame(1, 101),
+                Chunk::SkinObj(Clump {
+                    base_flags: 0,
+                    name_hash: 0,
+                    flags: 0,
+                    floor_flags: 0,
+                    // The third bone has no frame and gets a node of its own.
+                    bone_count: vec![bone(7), bone(8), bone(9)],
+                    has_hierarchy: true,
+                    default_animation_hash: 0,
+                    mirror_data: None,
+                }),
+                Chunk::SPMesh(ModelPart {
+                    vertices: vec![Vertex {
+                        vertex: Some(Vector3::new(1.0, 0.0, 0.0)),
+                        indices: Some((0, 1)),
+                        weight: Some(0.5),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            ],
+        };
+        let translations =
+            || AnimationKeys::Translations(vec![Vector3::default(), Vector3::new(0.0, 1.0, 0.0)]);
+        let animations = Bsp {
+            chunks: vec![
+                Chunk::AnimLib(AnimationDictionary {
+                    base_poses: Vec::new(),
+                    clip_count: 1,
+                }),
+                Chunk::Animation(Clips {
+                    name_hash: 0,
+                    minimum_time: 2.0,
+                    maximum_time: 3.0,
+                    base_poses: Vec::new(),
+                    sequence_count: 5,
+                    name: "walk".into(),
+                }),
+                key(8, translations()),
+                key(9, translations()),
+                // Both shape tracks animate the part under the second frame.
+                key(
+                    101,
+                    AnimationKeys::Shapes(vec![key_frame(1.0), key_frame(2.0)]),
+                ),
+                key(
+                    101,
+                    AnimationKeys::Shapes(vec![key_frame(1.0), key_frame(3.0)]),
+                ),
+                key(
+                    100,
+                    AnimationKeys::Shapes(vec![key_frame(1.0), key_frame(4.0)]),
+                ),
+            ],
+        };
+        let mut glb = Vec::new();
+
+        export_character_glb(&model, Some(&animations), &mut glb).unwrap();
+
+        let root = glb_json(&glb);
+        let nodes = root["nodes"].as_array().unwrap();
+
+        assert_eq!(root["skins"][0]["joints"], json!([0, 1, 2]));
+        assert_eq!(nodes[0]["children"], json!([1]));
+        assert_eq!(nodes[2]["name"], json!("bone_2"));
+        assert_eq!(nodes[3]["skin"], json!(0));
+        assert_eq!(
+            root["meshes"][0]["primitives"][0]["targets"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let animation = &root["animations"][0];
+        let channels = animation["channels"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|channel| {
+                (
+                    channel["target"]["node"].clone(),
+                    channel["target"]["path"].clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(animation["name"], json!("walk"));
+        // Key times are written as stored, like `AnimationLibrary::sample_bone` reads them.
+        assert_eq!(
+            root["accessors"][animation["samplers"][0]["input"].as_u64().unwrap() as usize]["max"],
+            json!([1.0])
+        );
+        assert_eq!(
+            channels,
+            vec![
+                (json!(1), json!("translation")),
+                (json!(2), json!("translation")),
+                (json!(3), json!("weights")),
+            ]
+        );
+
+        let mut animations = animations;
+
+        if let Chunk::Animation(clip) = &mut animations.chunks[1] {
+            clip.sequence_count += 1;
+        }
+
+        animations.chunks.push(key(9, translations()));
+
+        assert!(matches!(
+            export_character_glb(&model, Some(&animations), &mut Vec::new()),
+            Err(ExportError::DuplicateTrack { target_hash: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn merges_weights_of_shape_tracks() {
+        let tracks = [(vec![0.0, 1.0], 0), (vec![0.0, 2.0], 2)];
+        let (times, weights) = merge_weights(&tracks, 4);
+
+        assert_eq!(times, vec![0.0, 1.0, 2.0]);
+        assert_eq!(
+            weights,
+            vec![
+                1.0, 0.0, 1.0, 0.0, //
+                0.0, 1.0, 0.5, 0.5, //
+                0.0, 1.0, 0.0, 1.0,
+            ]
+        );
+    }
+}
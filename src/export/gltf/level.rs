@@ -0,0 +1,317 @@
+use super::{column_major, GltfBuilder};
+use crate::{frame_hierarchy, take_model_parts, Bsp, CameraProjection, Chunk, ExportError, Light};
+use serde_json::json;
+use std::{f32::consts::FRAC_PI_2, io::Write};
+
+/// Light types follow D3D's numbering, anything else is exported as a point light.
+const SPOT_LIGHT: i32 = 2;
+const DIRECTIONAL_LIGHT: i32 = 3;
+
+/// glTF lights and cameras look down -Z, while frames face along their `at` axis.
+const FACE_AT_AXIS: [f32; 4] = [0.0, 1.0, 0.0, 0.0];
+
+/// Writes the scene of a level file as binary glTF.
+///
+/// Every `BoneObj` frame becomes a node. A frame is parented to the last frame of the depth above
+/// the most recent `LevelObj` stream depth, and meshes, lights and cameras are attached to the frame
+/// that precedes them. Geometry following the `World` chunk is placed at the scene root.
+pub fn export_glb(bsp: &Bsp, writer: &mut impl Write) -> Result<(), ExportError> {
+    let mut builder = GltfBuilder::new(bsp);
+    let hierarchy = frame_hierarchy(&bsp.chunks);
+    let mut frames: Vec<usize> = Vec::with_capacity(hierarchy.len());
+    let mut current_frame = None;
+    let mut chunks = bsp.chunks.iter().peekable();
+
+    while let Some(chunk) = chunks.next() {
+        match chunk {
+            Chunk::World(_) => current_frame = None,
+            Chunk::BoneObj(frame) => {
+                let parent = hierarchy[frames.len()].parent.map(|parent| frames[parent]);
+                let matrix = if parent.is_some() {
+                    &frame.local_transform_matrix
+                } else {
+                    &frame.global_transform_matrix
+                };
+                let mut node = json!({ "matrix": column_major(matrix) });
+
+                if !frame.name.is_empty() {
+                    node["name"] = json!(frame.name);
+                }
+
+                let node = builder.push_node(node, parent);
+
+                frames.push(node);
+                current_frame = Some(node);
+            }
+            Chunk::ModelGroup(mesh) => {
+                let model_parts = take_model_parts(mesh, &mut chunks);
+
+                if let Some(mesh) = builder.mesh(None, &model_parts)? {
+                    builder.push_node(json!({ "mesh": mesh }), current_frame);
+                }
+            }
+            Chunk::SPMesh(model_part) => {
+                if let Some(mesh) = builder.mesh(None, &[model_part])? {
+                    builder.push_node(json!({ "mesh": mesh }), current_frame);
+                }
+            }
+            Chunk::LightObj(light) => {
+                builder.lights.push(light_json(light));
+                builder.use_extension("KHR_lights_punctual");
+
+                let light = builder.lights.len() - 1;
+
+                builder.push_node(
+                    json!({
+                        "rotation": FACE_AT_AXIS,
+                        "extensions": { "KHR_lights_punctual": { "light": light } },
+                    }),
+                    current_frame,
+                );
+            }
+            Chunk::GLCamera(camera_projection) | Chunk::GLProject(camera_projection) => {
+                builder.cameras.push(camera_json(camera_projection));
+
+                let camera = builder.cameras.len() - 1;
+
+                builder.push_node(
+                    json!({ "rotation": FACE_AT_AXIS, "camera": camera }),
+                    current_frame,
+                );
+            }
+            _ => (),
+        }
+    }
+
+    builder.write_glb(writer)
+}
+
+fn light_json(light: &Light) -> serde_json::Value {
+    let color = &light.light_color;
+    let mut value = json!({
+        "color": [
+            color.r as f32 / 255.0,
+            color.g as f32 / 255.0,
+            color.b as f32 / 255.0,
+        ],
+        "intensity": 1.0,
+    });
+
+    match light.light_type {
+        DIRECTIONAL_LIGHT => value["type"] = json!("directional"),
+        SPOT_LIGHT => {
+            // glTF measures the cone from its axis, D3D across the whole cone.
+            let outer_cone_angle = (light.cone_angle / 2.0).clamp(f32::EPSILON, FRAC_PI_2);
+
+            value["type"] = json!("spot");
+            value["spot"] = json!({
+                "innerConeAngle": 0.0,
+                "outerConeAngle": outer_cone_angle,
+            });
+        }
+        _ => value["type"] = json!("point"),
+    }
+
+    if light.light_type != DIRECTIONAL_LIGHT && light.radius > 0.0 {
+        value["range"] = json!(light.radius);
+    }
+
+    value
+}
+
+fn camera_json(camera_projection: &CameraProjection) -> serde_json::Value {
+    let rectangle = &camera_projection.rectangle;
+    let mut perspective = json!({
+        "yfov": camera_projection.angle_y.to_radians(),
+        "znear": camera_projection.near_z.max(f32::EPSILON),
+    });
+
+    if camera_projection.far_z > camera_projection.near_z {
+        perspective["zfar"] = json!(camera_projection.far_z);
+    }
+
+    if rectangle.width > 0 && rectangle.height > 0 {
+        perspective["aspectRatio"] = json!(rectangle.width as f32 / rectangle.height as f32);
+    }
+
+    json!({ "type": "perspective", "perspective": perspective })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::gltf::glb_json;
+    use crate::{
+        Frame, FrameChild, Material, MaterialTexture, Matrix, ModelPart, Rectangle, Rgba, Texture,
+        TextureAddress, TextureFilter, TextureFormat, Vector3, Vertex,
+    };
+    use serde_json::Value;
+    use std::f32::consts::{FRAC_PI_4, PI};
+
+    fn export(chunks: Vec<Chunk>) -> Value {
+        let mut glb = Vec::new();
+
+        export_glb(&Bsp { chunks }, &mut glb).unwrap();
+
+        glb_json(&glb)
+    }
+
+    #[test]
+    fn leaves_nodes_out_of_empty_scene() {
+        let root = export(Vec::new());
+
+        assert_eq!(root["scenes"], json!([{}]));
+        assert_eq!(root["nodes"], Value::Null);
+    }
+
+    #[test]
+    fn exports_lights_and_cameras_under_their_frame() {
+        let root = export(vec![
+            Chunk::LevelObj(FrameChild { stream_depth: 0 }),
+            Chunk::BoneObj(Frame {
+                local_transform_matrix: Matrix::identity(),
+                global_transform_matrix: Matrix::identity(),
+                bone_index: -1,
+                flags: 0,
+                id: 0,
+                name: "lamp".into(),
+            }),
+            Chunk::LightObj(Light {
+                base_flags: 0,
+                light_type: SPOT_LIGHT,
+                flags: 0,
+                radius: 10.0,
+                light_color: Rgba::new(255, 0, 0, 255),
+                cone_angle: PI / 2.0,
+                photon_light_abs_scale: 0.0,
+                light_switch_layer_index: None,
+            }),
+            Chunk::GLCamera(CameraProjection {
+                type_: 0,
+                near_z: 1.0,
+                far_z: 100.0,
+                angle_y: 90.0,
+                rectangle: Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 2,
+                },
+            }),
+        ]);
+        let light = &root["extensions"]["KHR_lights_punctual"]["lights"][0];
+        let camera = &root["cameras"][0]["perspective"];
+
+        assert_eq!(root["scenes"], json!([{ "nodes": [0] }]));
+        assert_eq!(root["nodes"][0]["children"], json!([1, 2]));
+        assert_eq!(light["type"], json!("spot"));
+        assert_eq!(light["spot"]["outerConeAngle"], json!(FRAC_PI_4));
+        assert_eq!(light["range"], json!(10.0));
+        assert_eq!(camera["yfov"], json!(90f32.to_radians()));
+        assert_eq!(camera["aspectRatio"], json!(2.0));
+    }
+
+    fn texture(name: &str, mask_name: &str, width: i32) -> Texture {
+        Texture {
+            name: name.into(),
+            mask_name: mask_name.into(),
+            width,
+            height: 1,
+            filter: TextureFilter::Linear,
+            address: TextureAddress::Wrap,
+            format: TextureFormat::R8G8B8,
+            border_color: Rgba::default(),
+            pixels: vec![Rgba::new(255, 255, 255, 255); width as usize],
+        }
+    }
+
+    /// A part with `uv_set_count` UV sets, using a material whose only texture is in its second
+    /// stage and reads the third UV set.
+    fn textured_chunks(uv_set_count: usize, textures: Vec<Texture>) -> Vec<Chunk> {
+        let mut material = Material {
+            material_hash: 7,
+            ..Default::default()
+        };
+
+        material.textures[1] = MaterialTexture {
+            uv_set: 2,
+            name: "wall".into(),
+            ..Default::default()
+        };
+
+        vec![
+            Chunk::MaterialObj(material),
+            Chunk::Textures(textures),
+            Chunk::SPMesh(ModelPart {
+                material_hash: 7,
+                vertices: vec![Vertex {
+                    vertex: Some(Vector3::default()),
+                    uvs: vec![(0.0, 0.0); uv_set_count],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+        ]
+    }
+
+    #[test]
+    fn uses_first_textured_stage_with_existing_uv_set() {
+        let textures = || vec![texture("wall", "", 1)];
+        let root = export(textured_chunks(2, textures()));
+        let primitive = &root["meshes"][0]["primitives"][0];
+        let base_color_texture = &root["materials"][0]["pbrMetallicRoughness"]["baseColorTexture"];
+
+        assert!(primitive["attributes"]["TEXCOORD_1"].is_u64());
+        assert_eq!(primitive["attributes"]["TEXCOORD_2"], Value::Null);
+        assert_eq!(base_color_texture["index"], json!(0));
+        assert_eq!(base_color_texture["texCoord"], json!(1));
+
+        let root = export(textured_chunks(0, textures()));
+
+        assert_eq!(
+            root["materials"][0]["pbrMetallicRoughness"]["baseColorTexture"],
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn rejects_mask_of_wrong_size() {
+        let chunks = textured_chunks(1, vec![texture("wall", "mask", 1), texture("mask", "", 2)]);
+
+        assert!(matches!(
+            export_glb(&Bsp { chunks }, &mut Vec::new()),
+            Err(ExportError::InvalidTexture { name, width: 2, .. }) if name == "mask"
+        ));
+    }
+
+    mod levels {
+        use super::SPOT_LIGHT;
+        use crate::{bsp::decode_level, Chunk};
+        use std::f32::consts::PI;
+        use test_case::test_case;
+
+        /// Checks the assumed angle units against real levels: spot light cones are at most a
+        /// half turn in radians, and camera fields of view are too large for radians but below a
+        /// half turn in degrees.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_angle_units(asset: &str) {
+            for chunk in &decode_level(asset).chunks {
+                match chunk {
+                    Chunk::LightObj(light) if light.light_type == SPOT_LIGHT => {
+                        assert!(
+                            light.cone_angle > 0.0 && light.cone_angle <= PI,
+                            "{light:?}"
+                        );
+                    }
+                    Chunk::GLCamera(camera) | Chunk::GLProject(camera) => {
+                        assert!(camera.angle_y > PI && camera.angle_y < 180.0, "{camera:?}");
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,617 @@
+mod character;
+mod level;
+
+pub use character::*;
+pub use level::*;
+
+use super::first_textured_stage;
+use crate::{
+    find_texture, Bsp, Chunk, ExportError, Material, Matrix, ModelPart, Texture, TextureAddress,
+    TextureFilter,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, io::Write};
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const GLB_VERSION: u32 = 2;
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A;
+const GLB_BIN_CHUNK: u32 = 0x004E4942;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+const UNSIGNED_BYTE: u32 = 5121;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const NEAREST: u32 = 9728;
+const LINEAR: u32 = 9729;
+const REPEAT: u32 = 10497;
+const CLAMP_TO_EDGE: u32 = 33071;
+const MIRRORED_REPEAT: u32 = 33648;
+
+/// Accumulates the JSON arrays and the binary buffer of a glTF asset.
+#[derive(Default)]
+struct GltfBuilder<'a> {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    images: Vec<Value>,
+    samplers: Vec<Value>,
+    textures: Vec<Value>,
+    materials: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+    cameras: Vec<Value>,
+    lights: Vec<Value>,
+    skins: Vec<Value>,
+    animations: Vec<Value>,
+    scene_nodes: Vec<usize>,
+    extensions_used: Vec<&'static str>,
+    source_materials: HashMap<u32, &'a Material>,
+    source_textures: Vec<&'a [Texture]>,
+    /// Keyed by material hash and the `texCoord` of its base color texture.
+    material_indices: HashMap<(u32, Option<usize>), usize>,
+    texture_indices: HashMap<&'a str, usize>,
+}
+
+impl<'a> GltfBuilder<'a> {
+    fn new(bsp: &'a Bsp) -> Self {
+        let mut builder = Self::default();
+
+        for chunk in &bsp.chunks {
+            match chunk {
+                Chunk::MaterialObj(material) => {
+                    builder
+                        .source_materials
+                        .insert(material.material_hash, material);
+                }
+                Chunk::Textures(textures) => builder.source_textures.push(textures),
+                _ => (),
+            }
+        }
+
+        builder
+    }
+
+    fn push_buffer_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        while !self.buffer.len().is_multiple_of(4) {
+            self.buffer.push(0);
+        }
+
+        let mut buffer_view = json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+        });
+
+        if let Some(target) = target {
+            buffer_view["target"] = json!(target);
+        }
+
+        self.buffer.extend_from_slice(bytes);
+        self.buffer_views.push(buffer_view);
+
+        self.buffer_views.len() - 1
+    }
+
+    fn push_accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+
+        self.accessors.len() - 1
+    }
+
+    /// Pushes tightly packed floats, `components` per element, optionally recording their bounds.
+    fn push_floats(
+        &mut self,
+        values: &[f32],
+        components: usize,
+        target: Option<u32>,
+        with_bounds: bool,
+    ) -> usize {
+        let mut bytes = Vec::with_capacity(values.len() * 4);
+
+        for value in values {
+            bytes.write_f32::<LittleEndian>(*value).unwrap();
+        }
+
+        let buffer_view = self.push_buffer_view(&bytes, target);
+        let mut accessor = json!({
+            "bufferView": buffer_view,
+            "componentType": FLOAT,
+            "count": values.len() / components,
+            "type": accessor_type(components),
+        });
+
+        if with_bounds {
+            let mut minimum = vec![f32::MAX; components];
+            let mut maximum = vec![f32::MIN; components];
+
+            for element in values.chunks(components) {
+                for (component, value) in element.iter().enumerate() {
+                    minimum[component] = minimum[component].min(*value);
+                    maximum[component] = maximum[component].max(*value);
+                }
+            }
+
+            accessor["min"] = json!(minimum);
+            accessor["max"] = json!(maximum);
+        }
+
+        self.push_accessor(accessor)
+    }
+
+    fn push_normalized_bytes(&mut self, values: &[u8], components: usize) -> usize {
+        let buffer_view = self.push_buffer_view(values, Some(ARRAY_BUFFER));
+
+        self.push_accessor(json!({
+            "bufferView": buffer_view,
+            "componentType": UNSIGNED_BYTE,
+            "normalized": true,
+            "count": values.len() / components,
+            "type": accessor_type(components),
+        }))
+    }
+
+    fn push_shorts(&mut self, values: &[u16], components: usize) -> usize {
+        let mut bytes = Vec::with_capacity(values.len() * 2);
+
+        for value in values {
+            bytes.write_u16::<LittleEndian>(*value).unwrap();
+        }
+
+        let buffer_view = self.push_buffer_view(&bytes, Some(ARRAY_BUFFER));
+
+        self.push_accessor(json!({
+            "bufferView": buffer_view,
+            "componentType": UNSIGNED_SHORT,
+            "count": values.len() / components,
+            "type": accessor_type(components),
+        }))
+    }
+
+    fn push_indices(&mut self, values: &[u32]) -> usize {
+        let mut bytes = Vec::with_capacity(values.len() * 4);
+
+        for value in values {
+            bytes.write_u32::<LittleEndian>(*value).unwrap();
+        }
+
+        let buffer_view = self.push_buffer_view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+
+        self.push_accessor(json!({
+            "bufferView": buffer_view,
+            "componentType": UNSIGNED_INT,
+            "count": values.len(),
+            "type": "SCALAR",
+        }))
+    }
+
+    fn push_node(&mut self, node: Value, parent: Option<usize>) -> usize {
+        self.nodes.push(node);
+
+        let index = self.nodes.len() - 1;
+
+        match parent {
+            Some(parent) => {
+                let node = self.nodes[parent].as_object_mut().unwrap();
+
+                node.entry("children")
+                    .or_insert_with(|| json!([]))
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json!(index));
+            }
+            None => self.scene_nodes.push(index),
+        }
+
+        index
+    }
+
+    fn use_extension(&mut self, extension: &'static str) {
+        if !self.extensions_used.contains(&extension) {
+            self.extensions_used.push(extension);
+        }
+    }
+
+    fn texture(&mut self, name: &'a str) -> Result<Option<usize>, ExportError> {
+        if let Some(index) = self.texture_indices.get(name) {
+            return Ok(Some(*index));
+        }
+
+        let mut texture = match self
+            .source_textures
+            .iter()
+            .find_map(|textures| find_texture(textures, name))
+        {
+            Some(texture) => texture.clone(),
+            None => return Ok(None),
+        };
+
+        if let Some(mask) = self
+            .source_textures
+            .iter()
+            .find_map(|textures| find_texture(textures, &texture.mask_name))
+            .filter(|_| texture.has_mask())
+        {
+            texture
+                .apply_mask(mask)
+                .map_err(|_| ExportError::InvalidTexture {
+                    name: mask.name.clone(),
+                    width: mask.width,
+                    height: mask.height,
+                })?;
+        }
+
+        let mut png = Vec::new();
+
+        texture.write_png(&mut png)?;
+
+        let buffer_view = self.push_buffer_view(&png, None);
+
+        self.images.push(json!({
+            "name": texture.name,
+            "bufferView": buffer_view,
+            "mimeType": "image/png",
+        }));
+
+        let sampler = sampler_json(&texture);
+        let sampler = match self.samplers.iter().position(|other| *other == sampler) {
+            Some(index) => index,
+            None => {
+                self.samplers.push(sampler);
+
+                self.samplers.len() - 1
+            }
+        };
+
+        self.textures.push(json!({
+            "source": self.images.len() - 1,
+            "sampler": sampler,
+        }));
+
+        let index = self.textures.len() - 1;
+
+        self.texture_indices.insert(name, index);
+
+        Ok(Some(index))
+    }
+
+    /// Converts the material of a primitive with `uv_set_count` `TEXCOORD_n` attributes. As in the
+    /// OBJ exporter, a stage using a missing UV set falls back to the last one, and no texture is
+    /// referenced without any.
+    fn material(&mut self, material_hash: u32, uv_set_count: usize) -> Result<usize, ExportError> {
+        let source_material = self.source_materials.get(&material_hash).copied();
+        let stage = source_material.and_then(first_textured_stage);
+        let tex_coord = stage
+            .filter(|_| uv_set_count > 0)
+            .map(|(_, texture)| (texture.uv_set as usize).min(uv_set_count - 1));
+
+        if let Some(index) = self.material_indices.get(&(material_hash, tex_coord)) {
+            return Ok(*index);
+        }
+
+        let mut value = json!({
+            "name": format!("material_{:08x}", material_hash),
+            "pbrMetallicRoughness": {
+                "metallicFactor": 0.0,
+            },
+        });
+
+        if let Some(material) = source_material {
+            let color = &material.color;
+
+            value["pbrMetallicRoughness"]["baseColorFactor"] = json!([
+                color.r as f32 / 255.0,
+                color.g as f32 / 255.0,
+                color.b as f32 / 255.0,
+                if material.blend {
+                    color.a as f32 / 255.0
+                } else {
+                    1.0
+                },
+            ]);
+            value["pbrMetallicRoughness"]["roughnessFactor"] =
+                json!((2.0 / (material.power.max(0.0) + 2.0)).sqrt());
+
+            if let (Some((_, stage)), Some(tex_coord)) = (stage, tex_coord) {
+                if let Some(texture) = self.texture(stage.name.as_str())? {
+                    value["pbrMetallicRoughness"]["baseColorTexture"] = json!({
+                        "index": texture,
+                        "texCoord": tex_coord,
+                    });
+                }
+            }
+
+            let pipeline_state = material.pipeline_state();
+
+            if let Some(alpha_test) = pipeline_state.alpha_test {
+                value["alphaMode"] = json!("MASK");
+                value["alphaCutoff"] = json!(alpha_test.cutoff);
+            } else if pipeline_state.is_translucent() {
+                value["alphaMode"] = json!("BLEND");
+            }
+        }
+
+        self.materials.push(value);
+
+        let index = self.materials.len() - 1;
+
+        self.material_indices
+            .insert((material_hash, tex_coord), index);
+
+        Ok(index)
+    }
+
+    /// Converts model parts into the primitives of one mesh, returning `None` if none has geometry.
+    fn mesh(
+        &mut self,
+        name: Option<&str>,
+        model_parts: &[&ModelPart],
+    ) -> Result<Option<usize>, ExportError> {
+        let mut primitives = Vec::new();
+
+        for model_part in model_parts {
+            if let Some(primitive) = self.primitive(model_part)? {
+                primitives.push(primitive);
+            }
+        }
+
+        if primitives.is_empty() {
+            return Ok(None);
+        }
+
+        let mut mesh = json!({ "primitives": primitives });
+
+        if let Some(name) = name {
+            mesh["name"] = json!(name);
+        }
+
+        self.meshes.push(mesh);
+
+        Ok(Some(self.meshes.len() - 1))
+    }
+
+    fn primitive(&mut self, model_part: &ModelPart) -> Result<Option<Value>, ExportError> {
+        let vertices = &model_part.vertices;
+
+        if vertices.is_empty() || vertices.iter().any(|vertex| vertex.vertex.is_none()) {
+            return Ok(None);
+        }
+
+        let mut attributes = Map::new();
+
+        let positions = vertices
+            .iter()
+            .flat_map(|vertex| {
+                let position = vertex.vertex.unwrap();
+
+                [position.x, position.y, position.z]
+            })
+            .collect::<Vec<_>>();
+
+        attributes.insert(
+            "POSITION".into(),
+            json!(self.push_floats(&positions, 3, Some(ARRAY_BUFFER), true)),
+        );
+
+        if vertices.iter().all(|vertex| vertex.normal.is_some()) {
+            let normals = vertices
+                .iter()
+                .flat_map(|vertex| {
+                    let normal = vertex.normal.unwrap();
+
+                    [normal.x, normal.y, normal.z]
+                })
+                .collect::<Vec<_>>();
+
+            attributes.insert(
+                "NORMAL".into(),
+                json!(self.push_floats(&normals, 3, Some(ARRAY_BUFFER), false)),
+            );
+        }
+
+        let uv_set_count = vertices
+            .iter()
+            .map(|vertex| vertex.uvs.len())
+            .min()
+            .unwrap_or(0);
+
+        for set in 0..uv_set_count {
+            let uvs = vertices
+                .iter()
+                .flat_map(|vertex| {
+                    let (u, v) = vertex.uvs[set];
+
+                    [u, v]
+                })
+                .collect::<Vec<_>>();
+
+            attributes.insert(
+                format!("TEXCOORD_{}", set),
+                json!(self.push_floats(&uvs, 2, Some(ARRAY_BUFFER), false)),
+            );
+        }
+
+        if vertices.iter().all(|vertex| vertex.diffuse.is_some()) {
+            let colors = vertices
+                .iter()
+                .flat_map(|vertex| {
+                    let diffuse = vertex.diffuse.as_ref().unwrap();
+
+                    [diffuse.r, diffuse.g, diffuse.b, diffuse.a]
+                })
+                .collect::<Vec<_>>();
+
+            attributes.insert(
+                "COLOR_0".into(),
+                json!(self.push_normalized_bytes(&colors, 4)),
+            );
+        }
+
+        let indices = model_part
+            .indices
+            .iter()
+            .flat_map(|index| [index.index0, index.index1, index.index2])
+            .collect::<Vec<_>>();
+
+        let mut primitive = json!({
+            "attributes": attributes,
+            "material": self.material(model_part.material_hash, uv_set_count)?,
+        });
+
+        if !indices.is_empty() {
+            primitive["indices"] = json!(self.push_indices(&indices));
+        }
+
+        Ok(Some(primitive))
+    }
+
+    fn write_glb(self, writer: &mut impl Write) -> Result<(), ExportError> {
+        let mut root = json!({
+            "asset": {
+                "version": "2.0",
+                "generator": "spooky_bsp",
+            },
+            "scene": 0,
+            "scenes": [{}],
+        });
+
+        // glTF requires a scene's node list to be non-empty when present.
+        if !self.scene_nodes.is_empty() {
+            root["scenes"][0]["nodes"] = json!(self.scene_nodes);
+        }
+
+        for (key, values) in [
+            ("bufferViews", self.buffer_views),
+            ("accessors", self.accessors),
+            ("images", self.images),
+            ("samplers", self.samplers),
+            ("textures", self.textures),
+            ("materials", self.materials),
+            ("meshes", self.meshes),
+            ("nodes", self.nodes),
+            ("cameras", self.cameras),
+            ("skins", self.skins),
+            ("animations", self.animations),
+        ] {
+            if !values.is_empty() {
+                root[key] = Value::Array(values);
+            }
+        }
+
+        if !self.lights.is_empty() {
+            root["extensions"] = json!({
+                "KHR_lights_punctual": { "lights": self.lights },
+            });
+        }
+
+        if !self.extensions_used.is_empty() {
+            root["extensionsUsed"] = json!(self.extensions_used);
+        }
+
+        let mut buffer = self.buffer;
+
+        if !buffer.is_empty() {
+            root["buffers"] = json!([{ "byteLength": buffer.len() }]);
+        }
+
+        let mut json = serde_json::to_vec(&root)?;
+
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+
+        while !buffer.len().is_multiple_of(4) {
+            buffer.push(0);
+        }
+
+        let mut length = 12 + 8 + json.len();
+
+        if !buffer.is_empty() {
+            length += 8 + buffer.len();
+        }
+
+        writer.write_u32::<LittleEndian>(GLB_MAGIC)?;
+        writer.write_u32::<LittleEndian>(GLB_VERSION)?;
+        writer.write_u32::<LittleEndian>(length as u32)?;
+
+        writer.write_u32::<LittleEndian>(json.len() as u32)?;
+        writer.write_u32::<LittleEndian>(GLB_JSON_CHUNK)?;
+        writer.write_all(&json)?;
+
+        if !buffer.is_empty() {
+            writer.write_u32::<LittleEndian>(buffer.len() as u32)?;
+            writer.write_u32::<LittleEndian>(GLB_BIN_CHUNK)?;
+            writer.write_all(&buffer)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn accessor_type(components: usize) -> &'static str {
+    match components {
+        1 => "SCALAR",
+        2 => "VEC2",
+        3 => "VEC3",
+        4 => "VEC4",
+        16 => "MAT4",
+        _ => unreachable!("unsupported accessor component count {}", components),
+    }
+}
+
+fn sampler_json(texture: &Texture) -> Value {
+    let mut sampler = json!({});
+    let filter = match texture.filter {
+        TextureFilter::None | TextureFilter::Point => Some(NEAREST),
+        TextureFilter::Linear | TextureFilter::Anisotropic => Some(LINEAR),
+        TextureFilter::Unknown(_) => None,
+    };
+    let wrap = match texture.address {
+        TextureAddress::Wrap => Some(REPEAT),
+        TextureAddress::Mirror => Some(MIRRORED_REPEAT),
+        TextureAddress::Clamp | TextureAddress::Border | TextureAddress::MirrorOnce => {
+            Some(CLAMP_TO_EDGE)
+        }
+        TextureAddress::Unknown(_) => None,
+    };
+
+    if let Some(filter) = filter {
+        sampler["magFilter"] = json!(filter);
+        sampler["minFilter"] = json!(filter);
+    }
+
+    if let Some(wrap) = wrap {
+        sampler["wrapS"] = json!(wrap);
+        sampler["wrapT"] = json!(wrap);
+    }
+
+    sampler
+}
+
+/// Converts a row-vector matrix into glTF's column-major layout.
+fn column_major(matrix: &Matrix) -> [f32; 16] {
+    let Matrix {
+        right,
+        up,
+        at,
+        position,
+        ..
+    } = matrix;
+
+    [
+        right.x, right.y, right.z, 0.0, up.x, up.y, up.z, 0.0, at.x, at.y, at.z, 0.0, position.x,
+        position.y, position.z, 1.0,
+    ]
+}
+
+/// Parses the JSON chunk of a binary glTF file.
+#[cfg(test)]
+pub(crate) fn glb_json(glb: &[u8]) -> Value {
+    let length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+
+    serde_json::from_slice(&glb[20..20 + length]).unwrap()
+}
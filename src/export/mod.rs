@@ -0,0 +1,83 @@
+mod gltf;
+mod obj;
+mod texture;
+
+pub use gltf::*;
+pub use obj::*;
+pub use texture::*;
+
+use crate::{AnimationLibraryError, Material, MaterialTexture, ShapeError};
+use std::io;
+
+#[derive(Debug)]
+pub enum ExportError {
+    InvalidTexture {
+        name: String,
+        width: i32,
+        height: i32,
+    },
+    /// The model part, counted among the `SPMesh` chunks, has vertices without a position.
+    MissingPositions {
+        part_index: usize,
+    },
+    /// A clip has two rotation or two translation tracks driving the same node.
+    DuplicateTrack {
+        clip: String,
+        target_hash: u32,
+    },
+    Animation(AnimationLibraryError),
+    Shape(ShapeError),
+    Json(serde_json::Error),
+    Png(png::EncodingError),
+    IO(io::Error),
+}
+
+impl From<io::Error> for ExportError {
+    fn from(error: io::Error) -> Self {
+        Self::IO(error)
+    }
+}
+
+impl From<AnimationLibraryError> for ExportError {
+    fn from(error: AnimationLibraryError) -> Self {
+        Self::Animation(error)
+    }
+}
+
+impl From<ShapeError> for ExportError {
+    fn from(error: ShapeError) -> Self {
+        Self::Shape(error)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl From<png::EncodingError> for ExportError {
+    fn from(error: png::EncodingError) -> Self {
+        Self::Png(error)
+    }
+}
+
+/// The first stage of `material` that has a texture, with its index. Exporters treat it as the
+/// diffuse map.
+pub(crate) fn first_textured_stage(material: &Material) -> Option<(usize, &MaterialTexture)> {
+    material
+        .textures
+        .iter()
+        .enumerate()
+        .find(|(_, texture)| !texture.name.is_empty())
+}
+
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|character| match character {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            character if character.is_control() => '_',
+            character => character,
+        })
+        .collect()
+}
@@ -0,0 +1,412 @@
+use super::{first_textured_stage, sanitize_file_name};
+use crate::{
+    extract_textures, Bsp, Chunk, CollisionMesh, ExportError, ImageFormat, Material, ModelPart,
+    Rgba,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Writes `<name>.obj`, `<name>.mtl` and every texture of the file as PNG into `directory`.
+pub fn export_obj(bsp: &Bsp, directory: impl AsRef<Path>, name: &str) -> Result<(), ExportError> {
+    let directory = directory.as_ref();
+    let name = sanitize_file_name(name);
+    let material_library = format!("{}.mtl", name);
+
+    fs::create_dir_all(directory)?;
+
+    let mut obj = BufWriter::new(File::create(directory.join(format!("{}.obj", name)))?);
+    let mut mtl = BufWriter::new(File::create(directory.join(&material_library))?);
+
+    write_obj(bsp, &mut obj, &mut mtl, &material_library)?;
+
+    obj.flush()?;
+    mtl.flush()?;
+
+    extract_textures(bsp, directory, ImageFormat::Png)?;
+
+    Ok(())
+}
+
+/// Writes the geometry of every `SPMesh` chunk to `obj` and the materials it references to `mtl`.
+///
+/// Faces are grouped by `material_hash`. Every UV set is written, but since OBJ faces can only
+/// reference one texture coordinate each, faces use the set of the material's first textured
+/// stage, which is also its `map_Kd`. Parts with vertices lacking a position are rejected.
+pub fn write_obj(
+    bsp: &Bsp,
+    obj: &mut impl Write,
+    mtl: &mut impl Write,
+    material_library: &str,
+) -> Result<(), ExportError> {
+    let materials = bsp
+        .chunks
+        .iter()
+        .filter_map(|chunk| match chunk {
+            Chunk::MaterialObj(material) => Some((material.material_hash, material)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut groups: Vec<(u32, Vec<(usize, &ModelPart)>)> = Vec::new();
+
+    for (index, model_part) in bsp
+        .chunks
+        .iter()
+        .filter_map(|chunk| match chunk {
+            Chunk::SPMesh(model_part) => Some(model_part),
+            _ => None,
+        })
+        .enumerate()
+    {
+        match groups
+            .iter_mut()
+            .find(|(material_hash, _)| *material_hash == model_part.material_hash)
+        {
+            Some((_, model_parts)) => model_parts.push((index, model_part)),
+            None => groups.push((model_part.material_hash, vec![(index, model_part)])),
+        }
+    }
+
+    writeln!(obj, "mtllib {}", material_library)?;
+
+    let mut position_offset = 1;
+    let mut uv_offset = 1;
+    let mut normal_offset = 1;
+
+    for (material_hash, model_parts) in &groups {
+        let material = materials.get(material_hash).copied();
+        let uv_set = material
+            .and_then(first_textured_stage)
+            .map_or(0, |(_, texture)| texture.uv_set as usize);
+
+        for (index, model_part) in model_parts {
+            if model_part
+                .vertices
+                .iter()
+                .any(|vertex| vertex.vertex.is_none())
+            {
+                return Err(ExportError::MissingPositions { part_index: *index });
+            }
+
+            if model_part.vertices.is_empty() {
+                continue;
+            }
+
+            let vertex_count = model_part.vertices.len();
+            let uv_set_count = model_part
+                .vertices
+                .iter()
+                .map(|vertex| vertex.uvs.len())
+                .min()
+                .unwrap_or(0);
+            let has_normals = model_part
+                .vertices
+                .iter()
+                .all(|vertex| vertex.normal.is_some());
+
+            writeln!(obj, "o part_{}", index)?;
+
+            for vertex in &model_part.vertices {
+                let position = vertex.vertex.unwrap();
+
+                match &vertex.diffuse {
+                    Some(diffuse) => writeln!(
+                        obj,
+                        "v {} {} {} {} {} {}",
+                        position.x,
+                        position.y,
+                        position.z,
+                        diffuse.r as f32 / 255.0,
+                        diffuse.g as f32 / 255.0,
+                        diffuse.b as f32 / 255.0
+                    )?,
+                    None => writeln!(obj, "v {} {} {}", position.x, position.y, position.z)?,
+                }
+            }
+
+            for set in 0..uv_set_count {
+                writeln!(obj, "# uv set {}", set)?;
+
+                for vertex in &model_part.vertices {
+                    let (u, v) = vertex.uvs[set];
+
+                    writeln!(obj, "vt {} {}", u, 1.0 - v)?;
+                }
+            }
+
+            if has_normals {
+                for vertex in &model_part.vertices {
+                    let normal = vertex.normal.unwrap();
+
+                    writeln!(obj, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+                }
+            }
+
+            writeln!(obj, "usemtl {}", material_name(*material_hash))?;
+
+            let face_uv_offset = if uv_set_count > 0 {
+                Some(uv_offset + uv_set.min(uv_set_count - 1) * vertex_count)
+            } else {
+                None
+            };
+
+            for index in &model_part.indices {
+                write!(obj, "f")?;
+
+                for corner in [index.index0, index.index1, index.index2] {
+                    let corner = corner as usize;
+
+                    write!(obj, " {}", position_offset + corner)?;
+
+                    match (face_uv_offset, has_normals) {
+                        (Some(face_uv_offset), true) => write!(
+                            obj,
+                            "/{}/{}",
+                            face_uv_offset + corner,
+                            normal_offset + corner
+                        )?,
+                        (Some(face_uv_offset), false) => {
+                            write!(obj, "/{}", face_uv_offset + corner)?
+                        }
+                        (None, true) => write!(obj, "//{}", normal_offset + corner)?,
+                        (None, false) => (),
+                    }
+                }
+
+                writeln!(obj)?;
+            }
+
+            position_offset += vertex_count;
+            uv_offset += uv_set_count * vertex_count;
+
+            if has_normals {
+                normal_offset += vertex_count;
+            }
+        }
+
+        write_material(mtl, *material_hash, material)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a collision mesh to `obj`. Triangles switch `usemtl` with their material, named like
+/// the materials of `write_obj` so both files can be loaded side by side.
+pub fn write_collision_obj(mesh: &CollisionMesh, obj: &mut impl Write) -> Result<(), ExportError> {
+    let mut current_material = None;
+
+    writeln!(obj, "o collision")?;
+
+    for position in &mesh.positions {
+        writeln!(obj, "v {} {} {}", position.x, position.y, position.z)?;
+    }
+
+    for (triangle, material) in mesh.indices.iter().zip(&mesh.materials) {
+        if current_material != Some(material.material_hash) {
+            writeln!(obj, "usemtl {}", material_name(material.material_hash))?;
+
+            current_material = Some(material.material_hash);
+        }
+
+        writeln!(
+            obj,
+            "f {} {} {}",
+            triangle[0] + 1,
+            triangle[1] + 1,
+            triangle[2] + 1
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_material(
+    mtl: &mut impl Write,
+    material_hash: u32,
+    material: Option<&Material>,
+) -> Result<(), ExportError> {
+    writeln!(mtl, "newmtl {}", material_name(material_hash))?;
+
+    let material = match material {
+        Some(material) => material,
+        None => {
+            writeln!(mtl, "Kd 0.8 0.8 0.8")?;
+            writeln!(mtl)?;
+
+            return Ok(());
+        }
+    };
+
+    writeln!(mtl, "Kd {}", color(&material.color))?;
+    writeln!(mtl, "Ks {}", color(&material.specular))?;
+    writeln!(mtl, "Ns {}", material.power)?;
+
+    if material.blend {
+        writeln!(mtl, "d {}", material.color.a as f32 / 255.0)?;
+    }
+
+    let diffuse_stage = first_textured_stage(material).map(|(stage, _)| stage);
+
+    for (stage, texture) in material.textures.iter().enumerate() {
+        if texture.name.is_empty() {
+            continue;
+        }
+
+        let file_name = format!("{}.png", sanitize_file_name(&texture.name));
+
+        if Some(stage) == diffuse_stage {
+            writeln!(mtl, "map_Kd {}", file_name)?;
+
+            if let Some(mask_name) = texture.mask_name.as_ref().filter(|name| !name.is_empty()) {
+                writeln!(mtl, "map_d {}.png", sanitize_file_name(mask_name))?;
+            }
+        } else {
+            writeln!(
+                mtl,
+                "# stage {} (uv set {}): {}",
+                stage, texture.uv_set, file_name
+            )?;
+        }
+    }
+
+    writeln!(mtl)?;
+
+    Ok(())
+}
+
+fn material_name(material_hash: u32) -> String {
+    format!("material_{:08x}", material_hash)
+}
+
+fn color(color: &Rgba) -> String {
+    format!(
+        "{} {} {}",
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Index, MaterialTexture, Vector3, Vertex};
+
+    fn texture(name: &str, uv_set: u32) -> MaterialTexture {
+        MaterialTexture {
+            uv_set,
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    fn material() -> Material {
+        Material {
+            color: Rgba::new(255, 0, 0, 255),
+            specular: Rgba::default(),
+            power: 8.0,
+            material_hash: 42,
+            textures: [
+                MaterialTexture::default(),
+                texture("wall", 1),
+                texture("detail", 0),
+                MaterialTexture::default(),
+                MaterialTexture::default(),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn model_part(positions: [Option<Vector3>; 3]) -> ModelPart {
+        ModelPart {
+            material_hash: 42,
+            vertices: positions
+                .iter()
+                .enumerate()
+                .map(|(index, position)| Vertex {
+                    vertex: *position,
+                    normal: Some(Vector3::new(0.0, 0.0, 1.0)),
+                    uvs: vec![(0.5, 0.5), (index as f32 * 0.25, 0.0)],
+                    ..Default::default()
+                })
+                .collect(),
+            indices: vec![Index {
+                index0: 0,
+                index1: 1,
+                index2: 2,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn write(model_part: ModelPart) -> Result<(String, String), ExportError> {
+        let bsp = Bsp {
+            chunks: vec![Chunk::MaterialObj(material()), Chunk::SPMesh(model_part)],
+        };
+        let mut obj = Vec::new();
+        let mut mtl = Vec::new();
+
+        write_obj(&bsp, &mut obj, &mut mtl, "level.mtl")?;
+
+        Ok((
+            String::from_utf8(obj).unwrap(),
+            String::from_utf8(mtl).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn writes_obj_and_mtl() {
+        let (obj, mtl) = write(model_part([
+            Some(Vector3::new(0.0, 0.0, 0.0)),
+            Some(Vector3::new(1.0, 0.0, 0.0)),
+            Some(Vector3::new(0.0, 1.0, 0.0)),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            obj,
+            "mtllib level.mtl
+o part_0
+v 0 0 0
+v 1 0 0
+v 0 1 0
+# uv set 0
+vt 0.5 0.5
+vt 0.5 0.5
+vt 0.5 0.5
+# uv set 1
+vt 0 1
+vt 0.25 1
+vt 0.5 1
+vn 0 0 1
+vn 0 0 1
+vn 0 0 1
+usemtl material_0000002a
+f 1/4/1 2/5/2 3/6/3
+"
+        );
+        assert_eq!(
+            mtl,
+            "newmtl material_0000002a
+Kd 1 0 0
+Ks 0 0 0
+Ns 8
+map_Kd wall.png
+# stage 2 (uv set 0): detail.png
+
+"
+        );
+    }
+
+    #[test]
+    fn rejects_parts_without_positions() {
+        assert!(matches!(
+            write(model_part([Some(Vector3::default()), None, None])),
+            Err(ExportError::MissingPositions { part_index: 0 })
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use super::sanitize_file_name;
+use crate::{Bsp, Chunk, ExportError, Texture};
+use byteorder::{LittleEndian, WriteBytesExt};
+use png::{BitDepth, ColorType, Encoder};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+const TGA_UNCOMPRESSED_TRUE_COLOR: u8 = 2;
+const TGA_TOP_LEFT_ORIGIN: u8 = 0x20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Tga,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Tga => "tga",
+        }
+    }
+}
+
+impl Texture {
+    /// Writes the texture as an RGBA PNG, or as RGB if the format has no meaningful alpha.
+    pub fn write_png(&self, writer: impl Write) -> Result<(), ExportError> {
+        self.check_dimensions()?;
+
+        let has_alpha = self.has_alpha();
+        let mut encoder = Encoder::new(writer, self.width as u32, self.height as u32);
+
+        encoder.set_color(if has_alpha {
+            ColorType::Rgba
+        } else {
+            ColorType::Rgb
+        });
+        encoder.set_depth(BitDepth::Eight);
+
+        let channels = if has_alpha { 4 } else { 3 };
+        let mut data = Vec::with_capacity(self.pixels.len() * channels);
+
+        for pixel in &self.pixels {
+            data.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a][..channels]);
+        }
+
+        encoder.write_header()?.write_image_data(&data)?;
+
+        Ok(())
+    }
+
+    /// Writes the texture as an uncompressed 32 or 24 bit TGA.
+    pub fn write_tga(&self, mut writer: impl Write) -> Result<(), ExportError> {
+        self.check_dimensions()?;
+
+        let has_alpha = self.has_alpha();
+
+        writer.write_u8(0)?;
+        writer.write_u8(0)?;
+        writer.write_u8(TGA_UNCOMPRESSED_TRUE_COLOR)?;
+        writer.write_all(&[0; 5])?;
+        writer.write_u16::<LittleEndian>(0)?;
+        writer.write_u16::<LittleEndian>(0)?;
+        writer.write_u16::<LittleEndian>(self.width as u16)?;
+        writer.write_u16::<LittleEndian>(self.height as u16)?;
+
+        if has_alpha {
+            writer.write_u8(32)?;
+            writer.write_u8(TGA_TOP_LEFT_ORIGIN | 8)?;
+        } else {
+            writer.write_u8(24)?;
+            writer.write_u8(TGA_TOP_LEFT_ORIGIN)?;
+        }
+
+        for pixel in &self.pixels {
+            writer.write_all(&[pixel.b, pixel.g, pixel.r])?;
+
+            if has_alpha {
+                writer.write_u8(pixel.a)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_image(&self, writer: impl Write, format: ImageFormat) -> Result<(), ExportError> {
+        match format {
+            ImageFormat::Png => self.write_png(writer),
+            ImageFormat::Tga => self.write_tga(writer),
+        }
+    }
+
+    pub fn file_name(&self, extension: &str) -> String {
+        format!("{}.{}", sanitize_file_name(&self.name), extension)
+    }
+
+    fn check_dimensions(&self) -> Result<(), ExportError> {
+        if self.width <= 0
+            || self.height <= 0
+            || self.width > u16::MAX as i32
+            || self.height > u16::MAX as i32
+            || self.pixels.len() as u64 != self.width as u64 * self.height as u64
+        {
+            return Err(ExportError::InvalidTexture {
+                name: self.name.clone(),
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes every texture of every `Textures` chunk into `directory`, named after `Texture::name`,
+/// and returns the paths written.
+pub fn extract_textures(
+    bsp: &Bsp,
+    directory: impl AsRef<Path>,
+    format: ImageFormat,
+) -> Result<Vec<PathBuf>, ExportError> {
+    let directory = directory.as_ref();
+    let mut paths = Vec::new();
+
+    fs::create_dir_all(directory)?;
+
+    for textures in bsp.chunks.iter().filter_map(|chunk| match chunk {
+        Chunk::Textures(textures) => Some(textures),
+        _ => None,
+    }) {
+        for texture in textures {
+            let path = directory.join(texture.file_name(format.extension()));
+            let mut writer = BufWriter::new(File::create(&path)?);
+
+            texture.write_image(&mut writer, format)?;
+            writer.flush()?;
+
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rgba, TextureAddress, TextureFilter, TextureFormat};
+    use png::Decoder;
+    use std::env;
+
+    fn texture(name: &str, format: TextureFormat) -> Texture {
+        Texture {
+            name: name.into(),
+            mask_name: String::new(),
+            width: 2,
+            height: 1,
+            filter: TextureFilter::Linear,
+            address: TextureAddress::Wrap,
+            format,
+            border_color: Rgba::default(),
+            pixels: vec![Rgba::new(1, 2, 3, 4), Rgba::new(5, 6, 7, 8)],
+        }
+    }
+
+    #[test]
+    fn writes_png() {
+        let mut png = Vec::new();
+
+        texture("test", TextureFormat::R8G8B8)
+            .write_png(&mut png)
+            .unwrap();
+
+        let mut reader = Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(info.color_type, ColorType::Rgb);
+        assert_eq!(&data[..info.buffer_size()], &[1, 2, 3, 5, 6, 7]);
+    }
+
+    #[test]
+    fn writes_tga() {
+        let mut tga = Vec::new();
+
+        texture("test", TextureFormat::A8R8G8B8)
+            .write_tga(&mut tga)
+            .unwrap();
+
+        assert_eq!(tga[2], TGA_UNCOMPRESSED_TRUE_COLOR);
+        assert_eq!(&tga[12..18], &[2, 0, 1, 0, 32, TGA_TOP_LEFT_ORIGIN | 8]);
+        assert_eq!(&tga[18..], &[3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn rejects_mismatched_dimensions() {
+        let mut texture = texture("test", TextureFormat::R8G8B8);
+
+        texture.width = i32::MAX;
+        texture.height = i32::MAX;
+
+        assert!(matches!(
+            texture.write_png(Vec::new()),
+            Err(ExportError::InvalidTexture { .. })
+        ));
+    }
+
+    #[test]
+    fn extracts_textures() {
+        let directory = env::temp_dir().join(format!("spooky_bsp_textures_{}", std::process::id()));
+        let bsp = Bsp {
+            chunks: vec![Chunk::Textures(vec![
+                texture("a", TextureFormat::R8G8B8),
+                texture("b/c", TextureFormat::R8G8B8),
+            ])],
+        };
+
+        let paths = extract_textures(&bsp, &directory, ImageFormat::Tga).unwrap();
+
+        assert_eq!(
+            paths,
+            vec![directory.join("a.tga"), directory.join("b_c.tga")]
+        );
+        assert!(paths.iter().all(|path| path.is_file()));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
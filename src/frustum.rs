@@ -0,0 +1,88 @@
+use crate::{BoundingBox, Matrix, Plane, Vector3, Vector4};
+
+/// A convex volume bounded by planes facing into it, usually the view volume of a camera.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frustum {
+    pub planes: Vec<Plane>,
+}
+
+impl Frustum {
+    /// Extracts the left, right, bottom, top, near and far planes of a view projection matrix.
+    ///
+    /// Points are transformed as row vectors and the near plane maps to a depth of 0, as in
+    /// Direct3D.
+    pub fn from_view_projection(view_projection: &Matrix) -> Self {
+        let column = |select: fn(&Vector4) -> f32| {
+            Vector4::new(
+                select(&view_projection.right),
+                select(&view_projection.up),
+                select(&view_projection.at),
+                select(&view_projection.position),
+            )
+        };
+        let (x, y, z, w) = (
+            column(|row| row.x),
+            column(|row| row.y),
+            column(|row| row.z),
+            column(|row| row.w),
+        );
+        let plane = |sign: f32, column: &Vector4| {
+            Plane {
+                a: w.x + sign * column.x,
+                b: w.y + sign * column.y,
+                c: w.z + sign * column.z,
+                d: w.w + sign * column.w,
+            }
+            .normalized()
+        };
+        let near = Plane {
+            a: z.x,
+            b: z.y,
+            c: z.z,
+            d: z.w,
+        }
+        .normalized();
+
+        Self {
+            planes: vec![
+                plane(1.0, &x),
+                plane(-1.0, &x),
+                plane(1.0, &y),
+                plane(-1.0, &y),
+                near,
+                plane(-1.0, &z),
+            ],
+        }
+    }
+
+    pub fn contains(&self, point: &Vector3) -> bool {
+        self.planes.iter().all(|plane| plane.distance(point) >= 0.0)
+    }
+
+    /// Whether `bounds` may be visible. Boxes near the edges of the frustum can be reported even
+    /// though they lie just outside, which is harmless for culling.
+    pub fn intersects(&self, bounds: &BoundingBox) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane normal.
+            let corner = Vector3::new(
+                if plane.a >= 0.0 {
+                    bounds.supremum.x
+                } else {
+                    bounds.infimum.x
+                },
+                if plane.b >= 0.0 {
+                    bounds.supremum.y
+                } else {
+                    bounds.infimum.y
+                },
+                if plane.c >= 0.0 {
+                    bounds.supremum.z
+                } else {
+                    bounds.infimum.z
+                },
+            );
+
+            plane.distance(&corner) >= 0.0
+        })
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{Rgba, Texture, TextureAddress, TextureFilter, TextureFormat};
+use png::{ColorType, Decoder, Transformations};
+use std::io::{self, Read};
+
+pub const MAXIMUM_TEXTURE_SIZE: i32 = 2048;
+
+#[derive(Debug)]
+pub enum ImportError {
+    NotPowerOfTwo {
+        width: i32,
+        height: i32,
+    },
+    TooLarge {
+        width: i32,
+        height: i32,
+        maximum: i32,
+    },
+    UnsupportedColorType(ColorType),
+    Png(png::DecodingError),
+    IO(io::Error),
+}
+
+impl From<io::Error> for ImportError {
+    fn from(error: io::Error) -> Self {
+        Self::IO(error)
+    }
+}
+
+impl From<png::DecodingError> for ImportError {
+    fn from(error: png::DecodingError) -> Self {
+        Self::Png(error)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TextureImportOptions {
+    pub name: String,
+    pub format: TextureFormat,
+    pub filter: TextureFilter,
+    pub address: TextureAddress,
+    /// When set, the image's alpha channel is split into a separate greyscale texture of this name.
+    pub mask_name: Option<String>,
+    pub maximum_size: i32,
+}
+
+impl TextureImportOptions {
+    pub fn new(name: &str, format: TextureFormat) -> Self {
+        Self {
+            name: name.to_owned(),
+            format,
+            filter: TextureFilter::Linear,
+            address: TextureAddress::Wrap,
+            mask_name: None,
+            maximum_size: MAXIMUM_TEXTURE_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedTexture {
+    pub texture: Texture,
+    pub mask: Option<Texture>,
+}
+
+impl Texture {
+    /// Builds a texture from a PNG image, whose sides must be powers of two no larger than
+    /// `options.maximum_size`.
+    pub fn from_png(
+        reader: impl Read,
+        options: &TextureImportOptions,
+    ) -> Result<ImportedTexture, ImportError> {
+        let mut decoder = Decoder::new(reader);
+
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+        let mut reader = decoder.read_info()?;
+        // The header is checked before the frame is allocated and decoded, so oversized images
+        // are rejected cheaply.
+        let side = |side: u32| i32::try_from(side).unwrap_or(i32::MAX);
+        let width = side(reader.info().width);
+        let height = side(reader.info().height);
+
+        validate_dimensions(width, height, options.maximum_size)?;
+
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+
+        let data = &buffer[..info.buffer_size()];
+        let pixels = match info.color_type {
+            ColorType::Rgba => data
+                .chunks_exact(4)
+                .map(|pixel| Rgba::new(pixel[0], pixel[1], pixel[2], pixel[3]))
+                .collect::<Vec<_>>(),
+            ColorType::Rgb => data
+                .chunks_exact(3)
+                .map(|pixel| Rgba::new(pixel[0], pixel[1], pixel[2], u8::MAX))
+                .collect(),
+            ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .map(|pixel| Rgba::new(pixel[0], pixel[0], pixel[0], pixel[1]))
+                .collect(),
+            ColorType::Grayscale => data
+                .iter()
+                .map(|luminance| Rgba::new(*luminance, *luminance, *luminance, u8::MAX))
+                .collect(),
+            color_type => return Err(ImportError::UnsupportedColorType(color_type)),
+        };
+
+        let mut texture = Texture {
+            name: options.name.clone(),
+            mask_name: String::new(),
+            width,
+            height,
+            filter: options.filter,
+            address: options.address,
+            format: options.format,
+            border_color: Rgba::default(),
+            pixels,
+        };
+
+        let mask = options.mask_name.as_ref().map(|mask_name| {
+            let mask_pixels = texture
+                .pixels
+                .iter_mut()
+                .map(|pixel| {
+                    let alpha = pixel.a;
+
+                    pixel.a = u8::MAX;
+
+                    Rgba::new(alpha, alpha, alpha, u8::MAX)
+                })
+                .collect();
+
+            texture.mask_name = mask_name.clone();
+
+            Texture {
+                name: mask_name.clone(),
+                mask_name: String::new(),
+                pixels: mask_pixels,
+                ..texture.clone()
+            }
+        });
+
+        Ok(ImportedTexture { texture, mask })
+    }
+}
+
+fn validate_dimensions(width: i32, height: i32, maximum_size: i32) -> Result<(), ImportError> {
+    let is_power_of_two = |side: i32| side > 0 && side & (side - 1) == 0;
+
+    if !is_power_of_two(width) || !is_power_of_two(height) {
+        return Err(ImportError::NotPowerOfTwo { width, height });
+    }
+
+    if width > maximum_size || height > maximum_size {
+        return Err(ImportError::TooLarge {
+            width,
+            height,
+            maximum: maximum_size,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_matches, assert_ok};
+
+    fn texture(width: i32, height: i32) -> Texture {
+        Texture {
+            name: "source".into(),
+            mask_name: String::new(),
+            width,
+            height,
+            filter: TextureFilter::Point,
+            address: TextureAddress::Clamp,
+            format: TextureFormat::A8R8G8B8,
+            border_color: Rgba::default(),
+            pixels: (0..width * height)
+                .map(|index| Rgba::new(index as u8, (index * 2) as u8, 3, (index * 10) as u8))
+                .collect(),
+        }
+    }
+
+    fn png(texture: &Texture) -> Vec<u8> {
+        let mut png = Vec::new();
+
+        assert_ok!(texture.write_png(&mut png));
+
+        png
+    }
+
+    #[test]
+    fn round_trips_png() {
+        let source = texture(4, 2);
+        let options = TextureImportOptions::new("imported", TextureFormat::A8R8G8B8);
+        let imported = Texture::from_png(&png(&source)[..], &options).unwrap();
+
+        assert_eq!(imported.texture.name, "imported");
+        assert_eq!(imported.texture.pixels, source.pixels);
+        assert!(imported.mask.is_none());
+    }
+
+    #[test]
+    fn splits_mask() {
+        let source = texture(2, 2);
+        let mut options = TextureImportOptions::new("leaves", TextureFormat::R5G6B5);
+
+        options.mask_name = Some("leaves_mask".into());
+
+        let imported = Texture::from_png(&png(&source)[..], &options).unwrap();
+        let mask = imported.mask.unwrap();
+
+        assert_eq!(imported.texture.mask_name, "leaves_mask");
+        assert!(imported.texture.pixels.iter().all(|pixel| pixel.a == 255));
+        assert_eq!(mask.pixels[3], Rgba::new(30, 30, 30, 255));
+    }
+
+    #[test]
+    fn rejects_invalid_dimensions() {
+        let options = TextureImportOptions::new("imported", TextureFormat::A8R8G8B8);
+
+        assert_matches!(
+            Texture::from_png(&png(&texture(3, 2))[..], &options),
+            Err(ImportError::NotPowerOfTwo {
+                width: 3,
+                height: 2
+            })
+        );
+        assert_matches!(
+            Texture::from_png(&png(&texture(4096, 1))[..], &options),
+            Err(ImportError::TooLarge { .. })
+        );
+    }
+}
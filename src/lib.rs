@@ -1,21 +1,60 @@
 #![feature(associated_type_defaults)]
 
 mod algebra;
+mod animation;
+mod animation_library;
 mod bounding_box;
 mod bsp;
 mod chunk;
+mod collision_mesh;
+mod collision_query;
 mod color;
 mod decode;
+mod export;
+mod frustum;
 mod hash;
+mod import;
+mod lighting;
+mod mask;
+mod morph;
+mod ngon;
+mod occlusion_query;
+mod pathfinding;
+mod pipeline;
+mod sector_query;
+mod skinning;
+mod spline_curve;
+mod texture_coordinates;
 mod utils;
+mod waypoint_graph;
+mod zone_query;
 
 pub use algebra::*;
+pub use animation::*;
+pub use animation_library::*;
 pub use bounding_box::*;
 pub use bsp::*;
 pub use chunk::*;
+pub use collision_mesh::*;
+pub use collision_query::*;
 pub use color::*;
 pub use decode::*;
+pub use export::*;
+pub use frustum::*;
 pub use hash::*;
+pub use import::*;
+pub use lighting::*;
+pub use mask::*;
+pub use morph::*;
+pub use ngon::*;
+pub use occlusion_query::*;
+pub use pathfinding::*;
+pub use pipeline::*;
+pub use skinning::*;
+pub use spline_curve::*;
+pub use texture_coordinates::*;
 pub use utils::*;
+pub use waypoint_graph::*;
+pub use zone_query::*;
 
 pub use spooky_bsp_derive::Decode;
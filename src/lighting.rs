@@ -0,0 +1,449 @@
+use crate::{
+    find_texture, hash, Light, ModelPart, Rectangle, Rgba, SwitchableLightMap, SwitchableLights,
+    Texture,
+};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LightingError {
+    MissingLightMap { name: String, texture_hash: u32 },
+}
+
+/// Diffuse colors of a model part lit by switchable lights.
+#[derive(Clone, Debug)]
+pub struct VertexLighting<'a> {
+    pub model_part: &'a ModelPart,
+    pub is_world_geometry: bool,
+    /// One color per vertex of `model_part`.
+    pub colors: Vec<Rgba>,
+}
+
+/// Collects the switch layers of `lights`, typically the ones that are switched on.
+pub fn light_layers<'a>(lights: impl IntoIterator<Item = &'a Light>) -> HashSet<u32> {
+    lights
+        .into_iter()
+        .filter_map(|light| light.light_switch_layer_index)
+        .collect()
+}
+
+impl SwitchableLights {
+    /// Maps a layer index stored in the chunk to the light switch layer it belongs to.
+    pub fn remap_layer(&self, layer_index: u32) -> u32 {
+        self.layer_remap_table
+            .as_ref()
+            .and_then(|table| table.get(layer_index as usize))
+            .copied()
+            .unwrap_or(layer_index)
+    }
+
+    pub fn is_layer_active(&self, layer_index: u32, active_layers: &HashSet<u32>) -> bool {
+        active_layers.contains(&self.remap_layer(layer_index))
+    }
+
+    /// Adds the contribution of every active layer to `base`, the lightmap with all switchable
+    /// lights off.
+    ///
+    /// Colors are accumulated in linear space, obtained by raising them to `gamma_ramp_power`, and
+    /// converted back afterwards. Update blocks without a sub-rectangle cover the whole
+    /// `update_region`; both are assumed to be given in lightmap pixels, which
+    /// `matches_assumed_light_maps` checks against real levels.
+    pub fn evaluate_light_map(
+        &self,
+        light_map: &SwitchableLightMap,
+        base: &Texture,
+        active_layers: &HashSet<u32>,
+    ) -> Texture {
+        let mut accumulated = base
+            .pixels
+            .iter()
+            .map(|pixel| self.linearize(pixel))
+            .collect::<Vec<_>>();
+
+        for update_block in light_map
+            .update_blocks
+            .iter()
+            .filter(|update_block| self.is_layer_active(update_block.layer_index, active_layers))
+        {
+            let region = update_block
+                .update_sub_rectangle
+                .as_ref()
+                .unwrap_or(&light_map.update_region);
+
+            for (index, additive) in update_block.additive_data.iter().enumerate() {
+                if let Some(pixel) = pixel_index(base, region, index) {
+                    let additive = self.linearize(additive);
+
+                    for channel in 0..3 {
+                        accumulated[pixel][channel] += additive[channel];
+                    }
+                }
+            }
+        }
+
+        let mut texture = base.clone();
+
+        for (pixel, linear) in texture.pixels.iter_mut().zip(&accumulated) {
+            pixel.r = self.delinearize(linear[0]);
+            pixel.g = self.delinearize(linear[1]);
+            pixel.b = self.delinearize(linear[2]);
+        }
+
+        texture
+    }
+
+    /// Evaluates every switchable lightmap, looking the base lightmaps up in `textures`.
+    pub fn evaluate_light_maps(
+        &self,
+        textures: &[Texture],
+        active_layers: &HashSet<u32>,
+    ) -> Result<Vec<Texture>, LightingError> {
+        self.light_maps
+            .iter()
+            .map(|light_map| {
+                let base = light_map.find_texture(textures).ok_or_else(|| {
+                    LightingError::MissingLightMap {
+                        name: light_map.texture_name().to_owned(),
+                        texture_hash: light_map.texture_hash,
+                    }
+                })?;
+
+                Ok(self.evaluate_light_map(light_map, base, active_layers))
+            })
+            .collect()
+    }
+
+    /// Computes the diffuse colors of every model part affected by a switchable light, adding the
+    /// per-vertex updates of the active layers to the part's own colors.
+    ///
+    /// `light_data` is indexed by layer, and its vertex blocks refer to `material_blocks`, which
+    /// are matched to model parts through `ModelPart::lighting_sid`. Parts affected only by
+    /// inactive layers are returned with their own colors so that switching lights off can be
+    /// previewed as well.
+    pub fn evaluate_vertex_colors<'a>(
+        &self,
+        model_parts: impl IntoIterator<Item = &'a ModelPart>,
+        active_layers: &HashSet<u32>,
+    ) -> Vec<VertexLighting<'a>> {
+        let model_parts = model_parts
+            .into_iter()
+            .map(|model_part| (model_part.lighting_sid, model_part))
+            .collect::<HashMap<_, _>>();
+        let mut accumulated = HashMap::<usize, Vec<[f32; 3]>>::new();
+
+        for (layer_index, light_data) in self.light_data.iter().enumerate() {
+            let is_active = self.is_layer_active(layer_index as u32, active_layers);
+
+            for vertex_block in &light_data.vertex_blocks {
+                let material_block_index = vertex_block.material_block_index as usize;
+                let Some(model_part) = self
+                    .material_blocks
+                    .get(material_block_index)
+                    .and_then(|material_block| model_parts.get(&material_block.lighting_id))
+                else {
+                    continue;
+                };
+
+                let colors = accumulated.entry(material_block_index).or_insert_with(|| {
+                    model_part
+                        .vertices
+                        .iter()
+                        .map(|vertex| self.linearize(&vertex.diffuse.clone().unwrap_or_default()))
+                        .collect()
+                });
+
+                if !is_active {
+                    continue;
+                }
+
+                for update in &vertex_block.updates {
+                    if let Some(color) = colors.get_mut(update.vertex_index as usize) {
+                        let additive = self.linearize(&update.color);
+
+                        for channel in 0..3 {
+                            color[channel] += additive[channel];
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut lighting = accumulated
+            .into_iter()
+            .map(|(material_block_index, colors)| {
+                let material_block = &self.material_blocks[material_block_index];
+                let model_part = model_parts[&material_block.lighting_id];
+                let colors = model_part
+                    .vertices
+                    .iter()
+                    .zip(colors)
+                    .map(|(vertex, linear)| {
+                        let alpha = vertex.diffuse.as_ref().map_or(u8::MAX, |diffuse| diffuse.a);
+
+                        Rgba::new(
+                            self.delinearize(linear[0]),
+                            self.delinearize(linear[1]),
+                            self.delinearize(linear[2]),
+                            alpha,
+                        )
+                    })
+                    .collect();
+
+                (
+                    material_block_index,
+                    VertexLighting {
+                        model_part,
+                        is_world_geometry: material_block.is_world_geometry,
+                        colors,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        lighting.sort_by_key(|(material_block_index, _)| *material_block_index);
+        lighting.into_iter().map(|(_, lighting)| lighting).collect()
+    }
+
+    fn linearize(&self, color: &Rgba) -> [f32; 3] {
+        [color.r, color.g, color.b]
+            .map(|channel| (channel as f32 / 255.0).powf(self.gamma_ramp_power))
+    }
+
+    fn delinearize(&self, linear: f32) -> u8 {
+        (linear.max(0.0).powf(1.0 / self.gamma_ramp_power).min(1.0) * 255.0).round() as u8
+    }
+}
+
+impl SwitchableLightMap {
+    /// The lightmap texture name without the padding of its fixed size field.
+    pub fn texture_name(&self) -> &str {
+        self.name.trim_end_matches('\0')
+    }
+
+    pub fn find_texture<'a>(&self, textures: &'a [Texture]) -> Option<&'a Texture> {
+        find_texture(textures, self.texture_name()).or_else(|| {
+            textures
+                .iter()
+                .find(|texture| hash(texture.name.as_bytes()) == self.texture_hash)
+        })
+    }
+}
+
+fn pixel_index(texture: &Texture, region: &Rectangle, index: usize) -> Option<usize> {
+    if region.width <= 0 {
+        return None;
+    }
+
+    let x = region.x + index as i32 % region.width;
+    let y = region.y + index as i32 / region.width;
+
+    if x < 0 || y < 0 || x >= texture.width || y >= texture.height {
+        return None;
+    }
+
+    Some((y * texture.width + x) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        LightMapUpdateBlock, MaterialBlockSwitchInfo, SingleVertexSwitchBlock, SwitchableLightData,
+        TextureAddress, TextureFilter, TextureFormat, UpdateRGBA, Vertex,
+    };
+
+    fn light_map(blocks: Vec<LightMapUpdateBlock>) -> SwitchableLightMap {
+        SwitchableLightMap {
+            texture_hash: 0,
+            name: "lightmap\0\0\0\0".into(),
+            update_region: Rectangle {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 2,
+            },
+            update_blocks: blocks,
+        }
+    }
+
+    #[test]
+    fn adds_active_layers_inside_sub_rectangles() {
+        let lights = SwitchableLights {
+            gamma_ramp_power: 1.0,
+            layer_remap_table: Some(vec![7, 8]),
+            light_maps: vec![light_map(vec![
+                LightMapUpdateBlock {
+                    layer_index: 0,
+                    update_sub_rectangle: Some(Rectangle {
+                        x: 1,
+                        y: 1,
+                        width: 1,
+                        height: 1,
+                    }),
+                    additive_data: vec![Rgba::new(100, 0, 0, 0)],
+                },
+                LightMapUpdateBlock {
+                    layer_index: 1,
+                    update_sub_rectangle: None,
+                    additive_data: vec![Rgba::new(0, 50, 0, 0); 4],
+                },
+            ])],
+            light_data: Vec::new(),
+            material_blocks: Vec::new(),
+        };
+        let base = Texture {
+            name: "LIGHTMAP".into(),
+            mask_name: String::new(),
+            width: 2,
+            height: 2,
+            filter: TextureFilter::Linear,
+            address: TextureAddress::Clamp,
+            format: TextureFormat::X8R8G8B8,
+            border_color: Rgba::default(),
+            pixels: vec![Rgba::new(200, 10, 10, 255); 4],
+        };
+
+        let evaluated = lights
+            .evaluate_light_maps(&[base], &HashSet::from([7]))
+            .unwrap();
+
+        assert_eq!(evaluated[0].pixels[0], Rgba::new(200, 10, 10, 255));
+        assert_eq!(evaluated[0].pixels[3], Rgba::new(255, 10, 10, 255));
+    }
+
+    #[test]
+    fn adds_active_vertex_updates() {
+        let vertex_block = |material_block_index, color| SingleVertexSwitchBlock {
+            material_block_index,
+            updates: vec![UpdateRGBA {
+                vertex_index: 1,
+                color,
+            }],
+        };
+        let lights = SwitchableLights {
+            gamma_ramp_power: 1.0,
+            layer_remap_table: None,
+            light_maps: Vec::new(),
+            light_data: vec![
+                SwitchableLightData {
+                    dependent_light_maps: Vec::new(),
+                    vertex_blocks: vec![vertex_block(0, Rgba::new(0, 100, 0, 0))],
+                },
+                SwitchableLightData {
+                    dependent_light_maps: Vec::new(),
+                    vertex_blocks: vec![vertex_block(1, Rgba::new(100, 0, 0, 0))],
+                },
+            ],
+            material_blocks: vec![
+                MaterialBlockSwitchInfo {
+                    lighting_id: 5,
+                    is_world_geometry: true,
+                    vertices_count: 2,
+                },
+                MaterialBlockSwitchInfo {
+                    lighting_id: 6,
+                    is_world_geometry: false,
+                    vertices_count: 2,
+                },
+            ],
+        };
+        let model_part = |lighting_sid| {
+            let vertex = Vertex {
+                vertex: None,
+                normal: None,
+                reciprocal_homogeneous_w: None,
+                diffuse: Some(Rgba::new(10, 10, 10, 128)),
+                weight: None,
+                indices: None,
+                uvs: Vec::new(),
+            };
+
+            ModelPart {
+                lighting_sid,
+                vertices: vec![vertex.clone(), vertex],
+                ..Default::default()
+            }
+        };
+        let model_parts = [model_part(5), model_part(6)];
+
+        let lighting = lights.evaluate_vertex_colors(&model_parts, &HashSet::from([0]));
+
+        assert_eq!(lighting.len(), 2);
+        assert!(lighting[0].is_world_geometry);
+        assert_eq!(lighting[0].model_part.lighting_sid, 5);
+        assert_eq!(lighting[0].colors[0], Rgba::new(10, 10, 10, 128));
+        assert_eq!(lighting[0].colors[1], Rgba::new(10, 110, 10, 128));
+        assert!(!lighting[1].is_world_geometry);
+        assert_eq!(lighting[1].colors[1], Rgba::new(10, 10, 10, 128));
+    }
+    mod levels {
+        use crate::{bsp::decode_level, Chunk, Rectangle};
+        use test_case::test_case;
+
+        fn encloses(outer: &Rectangle, inner: &Rectangle) -> bool {
+            inner.x >= outer.x
+                && inner.y >= outer.y
+                && inner.x + inner.width <= outer.x + outer.width
+                && inner.y + inner.height <= outer.y + outer.height
+        }
+
+        /// Checks the assumed layout of switchable lightmaps against real levels: the gamma ramp
+        /// is a power between linear and the default of old files, every lightmap is found, its
+        /// update region lies within it and every sub-rectangle, taken in lightmap pixels, lies
+        /// within the update region.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_light_maps(asset: &str) {
+            let bsp = decode_level(asset);
+            let textures = bsp
+                .chunks
+                .iter()
+                .filter_map(|chunk| match chunk {
+                    Chunk::Textures(textures) => Some(textures.as_slice()),
+                    _ => None,
+                })
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+
+            for chunk in &bsp.chunks {
+                let Chunk::SpLights(lights) = chunk else {
+                    continue;
+                };
+
+                assert!(
+                    (1.0..=4.0).contains(&lights.gamma_ramp_power),
+                    "{}",
+                    lights.gamma_ramp_power
+                );
+
+                for light_map in &lights.light_maps {
+                    let base = light_map.find_texture(&textures).unwrap();
+                    let texture = Rectangle {
+                        x: 0,
+                        y: 0,
+                        width: base.width,
+                        height: base.height,
+                    };
+
+                    assert!(
+                        encloses(&texture, &light_map.update_region),
+                        "{:?}",
+                        light_map.update_region
+                    );
+
+                    for update_block in &light_map.update_blocks {
+                        if let Some(sub_rectangle) = &update_block.update_sub_rectangle {
+                            assert!(
+                                encloses(&light_map.update_region, sub_rectangle),
+                                "{sub_rectangle:?} outside {:?}",
+                                light_map.update_region
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
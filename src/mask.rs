@@ -0,0 +1,216 @@
+use crate::{MaterialTexture, Texture, TextureFormat};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MaskError {
+    MissingTexture {
+        name: String,
+    },
+    MissingMask {
+        texture: String,
+        mask: String,
+    },
+    DimensionMismatch {
+        texture: String,
+        mask: String,
+        texture_size: (i32, i32),
+        mask_size: (i32, i32),
+    },
+}
+
+impl Texture {
+    pub fn has_mask(&self) -> bool {
+        !self.mask_name.is_empty()
+    }
+
+    /// Replaces the alpha channel with the luminance of `mask`, which must have the same size.
+    pub fn apply_mask(&mut self, mask: &Texture) -> Result<(), MaskError> {
+        if self.width != mask.width
+            || self.height != mask.height
+            || self.pixels.len() != mask.pixels.len()
+        {
+            return Err(MaskError::DimensionMismatch {
+                texture: self.name.clone(),
+                mask: mask.name.clone(),
+                texture_size: (self.width, self.height),
+                mask_size: (mask.width, mask.height),
+            });
+        }
+
+        for (pixel, mask_pixel) in self.pixels.iter_mut().zip(&mask.pixels) {
+            pixel.a = luminance(mask_pixel.r, mask_pixel.g, mask_pixel.b);
+        }
+
+        self.format = TextureFormat::A8R8G8B8;
+
+        Ok(())
+    }
+
+    /// Returns a copy of the texture with its `mask_name` resolved against `textures` and merged
+    /// into the alpha channel. Textures without a mask are returned unchanged.
+    pub fn with_mask(&self, textures: &[Texture]) -> Result<Texture, MaskError> {
+        let mut texture = self.clone();
+
+        if self.has_mask() {
+            let mask =
+                find_texture(textures, &self.mask_name).ok_or_else(|| MaskError::MissingMask {
+                    texture: self.name.clone(),
+                    mask: self.mask_name.clone(),
+                })?;
+
+            texture.apply_mask(mask)?;
+        }
+
+        Ok(texture)
+    }
+}
+
+impl MaterialTexture {
+    /// Looks up the stage's texture in `textures` and merges the stage's mask, falling back to
+    /// the texture's own `mask_name` when the stage does not name one.
+    pub fn resolve(&self, textures: &[Texture]) -> Result<Texture, MaskError> {
+        let texture =
+            find_texture(textures, &self.name).ok_or_else(|| MaskError::MissingTexture {
+                name: self.name.clone(),
+            })?;
+
+        match self.mask_name.as_deref().filter(|name| !name.is_empty()) {
+            Some(mask_name) => {
+                let mask =
+                    find_texture(textures, mask_name).ok_or_else(|| MaskError::MissingMask {
+                        texture: self.name.clone(),
+                        mask: mask_name.to_owned(),
+                    })?;
+                let mut texture = texture.clone();
+
+                texture.apply_mask(mask)?;
+
+                Ok(texture)
+            }
+            None => texture.with_mask(textures),
+        }
+    }
+}
+
+/// Finds a texture by name, ignoring ASCII case like the game's file system does.
+pub fn find_texture<'a>(textures: &'a [Texture], name: &str) -> Option<&'a Texture> {
+    textures
+        .iter()
+        .find(|texture| texture.name.eq_ignore_ascii_case(name))
+}
+
+/// Merges the mask of every texture in `textures`, resolving masks in `textures` and then in
+/// `masks`. Textures whose mask cannot be applied are returned unchanged alongside the error.
+pub fn composite_masks(textures: &[Texture], masks: &[Texture]) -> (Vec<Texture>, Vec<MaskError>) {
+    let mut composited = Vec::with_capacity(textures.len());
+    let mut errors = Vec::new();
+
+    for texture in textures {
+        if !texture.has_mask() {
+            composited.push(texture.clone());
+
+            continue;
+        }
+
+        let result = match find_texture(textures, &texture.mask_name)
+            .or_else(|| find_texture(masks, &texture.mask_name))
+        {
+            Some(mask) => {
+                let mut masked = texture.clone();
+
+                masked.apply_mask(mask).map(|_| masked)
+            }
+            None => Err(MaskError::MissingMask {
+                texture: texture.name.clone(),
+                mask: texture.mask_name.clone(),
+            }),
+        };
+
+        match result {
+            Ok(masked) => composited.push(masked),
+            Err(error) => {
+                composited.push(texture.clone());
+                errors.push(error);
+            }
+        }
+    }
+
+    (composited, errors)
+}
+
+/// Rec. 601 luma, as used by Direct3D era tools.
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rgba, TextureAddress, TextureFilter};
+
+    fn texture(name: &str, mask_name: &str, width: i32, pixels: Vec<Rgba>) -> Texture {
+        Texture {
+            name: name.into(),
+            mask_name: mask_name.into(),
+            width,
+            height: pixels.len() as i32 / width,
+            filter: TextureFilter::Linear,
+            address: TextureAddress::Wrap,
+            format: TextureFormat::R5G6B5,
+            border_color: Rgba::default(),
+            pixels,
+        }
+    }
+
+    #[test]
+    fn composites_mask_luminance() {
+        let textures = vec![
+            texture(
+                "leaves",
+                "LEAVES_MASK",
+                2,
+                vec![Rgba::new(10, 20, 30, 255), Rgba::new(40, 50, 60, 255)],
+            ),
+            texture(
+                "leaves_mask",
+                "",
+                2,
+                vec![Rgba::new(0, 0, 0, 255), Rgba::new(255, 255, 255, 255)],
+            ),
+        ];
+
+        let (composited, errors) = composite_masks(&textures, &[]);
+
+        assert!(errors.is_empty());
+        assert_eq!(composited[0].pixels[0], Rgba::new(10, 20, 30, 0));
+        assert_eq!(composited[0].pixels[1], Rgba::new(40, 50, 60, 255));
+        assert!(composited[0].has_alpha());
+    }
+
+    #[test]
+    fn reports_missing_and_mismatched_masks() {
+        let textures = vec![
+            texture("a", "missing", 1, vec![Rgba::default()]),
+            texture("b", "c", 1, vec![Rgba::default()]),
+            texture("c", "", 2, vec![Rgba::default(), Rgba::default()]),
+        ];
+
+        let (composited, errors) = composite_masks(&textures, &[]);
+
+        assert_eq!(composited.len(), 3);
+        assert_eq!(
+            errors,
+            vec![
+                MaskError::MissingMask {
+                    texture: "a".into(),
+                    mask: "missing".into(),
+                },
+                MaskError::DimensionMismatch {
+                    texture: "b".into(),
+                    mask: "c".into(),
+                    texture_size: (1, 1),
+                    mask_size: (2, 1),
+                },
+            ]
+        );
+    }
+}
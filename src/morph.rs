@@ -0,0 +1,700 @@
+use crate::{
+    animation::{hermite, locate},
+    hash, take_model_parts, AdaptiveDifferentialPulseCodeModulation,
+    AdaptiveDifferentialPulseCodeModulationType, AnimationKey, AnimationKeys, BoundingBox, Chunk,
+    Frame, Interpolation, ModelPart, Shape, Vector3, WrapMode,
+};
+
+const COMPONENT_BITS: u32 = 5;
+const COMPONENT_MASK: u16 = (1 << COMPONENT_BITS) - 1;
+const COMPONENT_BIAS: i32 = 1 << (COMPONENT_BITS - 1);
+const EXPONENTIAL_STEPS: f32 = 8.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShapeError {
+    NotAShapeTrack,
+    /// A compressed shape precedes the first key framed shape it would be applied to.
+    MissingKeyFrame {
+        shape_index: usize,
+    },
+    /// The track has compressed shapes but no compression parameters.
+    MissingCompression,
+    /// A compressed shape changes a vertex the track does not animate.
+    IndexOutOfRange {
+        shape_index: usize,
+        index: usize,
+    },
+    /// The compressed shapes of a track animating part of its model part name a different number
+    /// of vertices than its key frames store.
+    UnboundVertices {
+        animated_count: usize,
+        index_count: usize,
+    },
+}
+
+/// The positions and normals of the animated vertices at one key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShapeFrame {
+    pub positions: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+}
+
+/// A model part of a ghost model, with the frame its mesh hangs off and its index among the
+/// material blocks of that mesh, which together name it in shape tracks.
+#[derive(Clone, Copy, Debug)]
+pub struct MeshPart<'a> {
+    pub frame: Option<&'a Frame>,
+    pub material_block_index: usize,
+    pub model_part: &'a ModelPart,
+}
+
+/// A shape track decoded once and bound to the vertices of the model part it deforms.
+#[derive(Clone, Debug)]
+pub struct ShapeTrack<'a> {
+    pub key: &'a AnimationKey,
+    pub frames: Vec<ShapeFrame>,
+    /// The vertex of the part moved by each animated vertex.
+    pub vertex_indices: Vec<usize>,
+    times: Vec<f32>,
+}
+
+/// The vertex data of a model part deformed by a shape track.
+#[derive(Clone, Debug)]
+pub struct DeformedVertices {
+    /// One position per vertex of the part.
+    pub positions: Vec<Vector3>,
+    /// One normal per vertex of the part, empty if the part has no normals.
+    pub normals: Vec<Vector3>,
+    /// Encloses the deformed positions.
+    pub bounds: Option<BoundingBox>,
+}
+
+impl AdaptiveDifferentialPulseCodeModulationType {
+    /// Maps a quantized component in `[-16, 15]` to `[-1, 1)`.
+    ///
+    /// `Linear` steps are evenly spaced, `Exponential` steps grow with magnitude so small deltas
+    /// keep their precision. `None` streams carry no deltas.
+    pub fn dequantize(&self, quantized: i32) -> f32 {
+        let normalized = quantized as f32 / COMPONENT_BIAS as f32;
+
+        match self {
+            Self::None => 0.0,
+            Self::Linear => normalized,
+            Self::Exponential => {
+                normalized.signum() * ((normalized.abs() * EXPONENTIAL_STEPS).exp2() - 1.0)
+                    / (EXPONENTIAL_STEPS.exp2() - 1.0)
+            }
+        }
+    }
+
+    /// Unpacks a delta stored as three biased 5 bit components, x in the lowest bits, scaled by
+    /// `range` per axis.
+    pub fn decode_delta(&self, element: u16, range: &Vector3) -> Vector3 {
+        let component = |shift: u32| {
+            let quantized = ((element >> shift) & COMPONENT_MASK) as i32 - COMPONENT_BIAS;
+
+            self.dequantize(quantized)
+        };
+
+        Vector3::new(
+            component(0) * range.x,
+            component(COMPONENT_BITS) * range.y,
+            component(COMPONENT_BITS * 2) * range.z,
+        )
+    }
+}
+
+impl AdaptiveDifferentialPulseCodeModulation {
+    pub fn vertex_delta(&self, element: u16) -> Vector3 {
+        self.vertex_type.decode_delta(element, &self.vertex_range)
+    }
+
+    pub fn normal_delta(&self, element: u16) -> Vector3 {
+        self.normal_type.decode_delta(element, &self.normal_range)
+    }
+}
+
+impl AnimationKey {
+    /// The vertex of a model part of `vertex_count` vertices moved by each animated vertex.
+    ///
+    /// Key framed shapes do not store which vertices they move, compressed shapes do. A track
+    /// storing every vertex of the part animates them in order; otherwise its animated vertices
+    /// are the vertices named by its compressed shapes, in increasing order.
+    /// `matches_assumed_targets` checks this binding against real files.
+    pub fn shape_vertex_indices(&self, vertex_count: usize) -> Result<Vec<usize>, ShapeError> {
+        let shapes = self.shapes()?;
+        let animated_count = shapes
+            .iter()
+            .find_map(|shape| match shape {
+                Shape::KeyFrame {
+                    animated_vertices, ..
+                } => Some(animated_vertices.elements.len()),
+                Shape::NotKeyFrame { .. } => None,
+            })
+            .ok_or(ShapeError::MissingKeyFrame { shape_index: 0 })?;
+
+        if animated_count == vertex_count {
+            return Ok((0..vertex_count).collect());
+        }
+
+        let mut vertex_indices = Vec::new();
+
+        for (shape_index, shape) in shapes.iter().enumerate() {
+            if let Shape::NotKeyFrame {
+                animated_vertices,
+                normals,
+            } = shape
+            {
+                for index in animated_vertices.indices.iter().chain(&normals.indices) {
+                    let index = *index as usize;
+
+                    if index >= vertex_count {
+                        return Err(ShapeError::IndexOutOfRange { shape_index, index });
+                    }
+
+                    vertex_indices.push(index);
+                }
+            }
+        }
+
+        vertex_indices.sort_unstable();
+        vertex_indices.dedup();
+
+        if vertex_indices.len() != animated_count {
+            return Err(ShapeError::UnboundVertices {
+                animated_count,
+                index_count: vertex_indices.len(),
+            });
+        }
+
+        Ok(vertex_indices)
+    }
+
+    /// Reconstructs every key of a `Shape` track whose animated vertices move `vertex_indices`,
+    /// as returned by `shape_vertex_indices`.
+    ///
+    /// Compressed shapes add their deltas to the previous frame, so every frame is rebuilt from
+    /// the last `Shape::KeyFrame` before it. Their indices name the vertices that change; the
+    /// others keep their previous value. The packing of the deltas is assumed;
+    /// `matches_assumed_compression` checks it against real files.
+    pub fn shape_frames(&self, vertex_indices: &[usize]) -> Result<Vec<ShapeFrame>, ShapeError> {
+        let shapes = self.shapes()?;
+        let mut frames: Vec<ShapeFrame> = Vec::with_capacity(shapes.len());
+
+        for (shape_index, shape) in shapes.iter().enumerate() {
+            let frame = match shape {
+                Shape::KeyFrame {
+                    animated_vertices,
+                    normals,
+                } => ShapeFrame {
+                    positions: animated_vertices.elements.clone(),
+                    normals: normals.elements.clone(),
+                },
+                Shape::NotKeyFrame {
+                    animated_vertices,
+                    normals,
+                } => {
+                    let compression = self
+                        .adaptive_differential_pulse_code_modulation
+                        .as_ref()
+                        .ok_or(ShapeError::MissingCompression)?;
+                    let mut frame = frames
+                        .last()
+                        .cloned()
+                        .ok_or(ShapeError::MissingKeyFrame { shape_index })?;
+
+                    accumulate(
+                        &mut frame.positions,
+                        vertex_indices,
+                        &animated_vertices.indices,
+                        &animated_vertices.elements,
+                        shape_index,
+                        |position, element| *position += compression.vertex_delta(element),
+                    )?;
+                    accumulate(
+                        &mut frame.normals,
+                        vertex_indices,
+                        &normals.indices,
+                        &normals.elements,
+                        shape_index,
+                        |normal, element| {
+                            *normal = (*normal + compression.normal_delta(element)).normalized()
+                        },
+                    )?;
+
+                    frame
+                }
+            };
+
+            frames.push(frame);
+        }
+
+        Ok(frames)
+    }
+
+    fn shapes(&self) -> Result<&[Shape], ShapeError> {
+        match &self.keys {
+            AnimationKeys::Shapes(shapes) => Ok(shapes),
+            _ => Err(ShapeError::NotAShapeTrack),
+        }
+    }
+
+    /// Whether this is a shape track of the model part at `material_block_index` in the mesh
+    /// named by `target_hash`.
+    pub fn is_shape_track_for(&self, target_hash: u32, material_block_index: usize) -> bool {
+        matches!(self.keys, AnimationKeys::Shapes(_))
+            && self.target_hash == target_hash
+            && self.material_block_index as usize == material_block_index
+    }
+}
+
+impl MeshPart<'_> {
+    /// Whether `key` is a shape track of this part. The mesh is assumed to be named by the id or
+    /// the hashed name of its frame, which `matches_assumed_targets` checks against real files.
+    pub fn is_animated_by(&self, key: &AnimationKey) -> bool {
+        self.frame.is_some_and(|frame| {
+            [frame.id, hash(frame.name.as_bytes())]
+                .into_iter()
+                .any(|target_hash| key.is_shape_track_for(target_hash, self.material_block_index))
+        })
+    }
+}
+
+/// The model parts of `chunks`, each owned by the last `BoneObj` frame before its mesh. A lone
+/// `SPMesh` counts as a mesh of one material block, and parts after the `World` chunk have no
+/// frame.
+pub fn mesh_parts(chunks: &[Chunk]) -> Vec<MeshPart<'_>> {
+    let mut mesh_parts = Vec::new();
+    let mut frame = None;
+    let mut chunks = chunks.iter().peekable();
+
+    while let Some(chunk) = chunks.next() {
+        match chunk {
+            Chunk::World(_) => frame = None,
+            Chunk::BoneObj(bone_frame) => frame = Some(bone_frame),
+            Chunk::ModelGroup(mesh) => {
+                for (material_block_index, model_part) in
+                    take_model_parts(mesh, &mut chunks).into_iter().enumerate()
+                {
+                    mesh_parts.push(MeshPart {
+                        frame,
+                        material_block_index,
+                        model_part,
+                    });
+                }
+            }
+            Chunk::SPMesh(model_part) => mesh_parts.push(MeshPart {
+                frame,
+                material_block_index: 0,
+                model_part,
+            }),
+            _ => (),
+        }
+    }
+
+    mesh_parts
+}
+
+impl<'a> ShapeTrack<'a> {
+    /// Finds the vertices of `model_part` that `key` animates and decodes its frames.
+    pub fn new(key: &'a AnimationKey, model_part: &ModelPart) -> Result<Self, ShapeError> {
+        let vertex_indices = key.shape_vertex_indices(model_part.vertices.len())?;
+        let frames = key.shape_frames(&vertex_indices)?;
+        let mut times = key.key_times();
+
+        times.truncate(frames.len());
+
+        Ok(Self {
+            key,
+            frames,
+            vertex_indices,
+            times,
+        })
+    }
+
+    /// Deforms `model_part`, the part the track was bound to, at `time`.
+    pub fn deform(
+        &self,
+        model_part: &ModelPart,
+        time: f32,
+        wrap_mode: WrapMode,
+    ) -> DeformedVertices {
+        let mut positions = model_part
+            .vertices
+            .iter()
+            .map(|vertex| vertex.vertex.unwrap_or_default())
+            .collect::<Vec<_>>();
+        let mut normals = model_part
+            .vertices
+            .iter()
+            .map(|vertex| vertex.normal)
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+
+        if let Some((index, t)) = locate(&self.times, time, wrap_mode) {
+            let animated_positions = self.interpolate(index, t, |frame| &frame.positions);
+
+            scatter(&mut positions, &self.vertex_indices, &animated_positions);
+
+            if self.frames.iter().all(|frame| !frame.normals.is_empty()) {
+                let animated_normals = self
+                    .interpolate(index, t, |frame| &frame.normals)
+                    .iter()
+                    .map(Vector3::normalized)
+                    .collect::<Vec<_>>();
+
+                scatter(&mut normals, &self.vertex_indices, &animated_normals);
+            }
+        }
+
+        DeformedVertices {
+            bounds: bounds(&positions),
+            positions,
+            normals,
+        }
+    }
+
+    fn interpolate(
+        &self,
+        index: usize,
+        t: f32,
+        elements: impl Fn(&ShapeFrame) -> &Vec<Vector3>,
+    ) -> Vec<Vector3> {
+        let next = (index + 1).min(self.times.len() - 1);
+
+        match self.key.interpolation_type {
+            Interpolation::Linear => elements(&self.frames[index])
+                .iter()
+                .zip(elements(&self.frames[next]))
+                .map(|(a, b)| a.lerp(b, t))
+                .collect(),
+            Interpolation::CubicSpline => {
+                let components = elements(&self.frames[0]).len() * 3;
+                let values = self
+                    .frames
+                    .iter()
+                    .flat_map(&elements)
+                    .flat_map(|element| [element.x, element.y, element.z])
+                    .collect::<Vec<_>>();
+
+                hermite(&self.times, &values, components, index, t)
+                    .chunks_exact(3)
+                    .map(|element| Vector3::new(element[0], element[1], element[2]))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Applies each compressed element to the frame element of the vertex its index names.
+fn accumulate(
+    frame: &mut [Vector3],
+    vertex_indices: &[usize],
+    indices: &[u16],
+    elements: &[u16],
+    shape_index: usize,
+    apply: impl Fn(&mut Vector3, u16),
+) -> Result<(), ShapeError> {
+    for (index, element) in indices.iter().zip(elements) {
+        let index = *index as usize;
+        let value = vertex_indices
+            .binary_search(&index)
+            .ok()
+            .and_then(|animated_index| frame.get_mut(animated_index))
+            .ok_or(ShapeError::IndexOutOfRange { shape_index, index })?;
+
+        apply(value, *element);
+    }
+
+    Ok(())
+}
+
+/// Writes `animated[i]` over `base[vertex_indices[i]]`.
+fn scatter(base: &mut [Vector3], vertex_indices: &[usize], animated: &[Vector3]) {
+    for (vertex_index, element) in vertex_indices.iter().zip(animated) {
+        if let Some(base) = base.get_mut(*vertex_index) {
+            *base = *element;
+        }
+    }
+}
+
+fn bounds(positions: &[Vector3]) -> Option<BoundingBox> {
+    let first = *positions.first()?;
+
+    Some(positions.iter().fold(
+        BoundingBox {
+            infimum: first,
+            supremum: first,
+        },
+        |bounds, position| BoundingBox {
+            infimum: Vector3::new(
+                bounds.infimum.x.min(position.x),
+                bounds.infimum.y.min(position.y),
+                bounds.infimum.z.min(position.z),
+            ),
+            supremum: Vector3::new(
+                bounds.supremum.x.max(position.x),
+                bounds.supremum.y.max(position.y),
+                bounds.supremum.z.max(position.z),
+            ),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Decode, KeyFrameAnimatedVertices, KeyFrameNormals, NotKeyFrameAnimatedVertices,
+        NotKeyFrameNormals, Vertex,
+    };
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    #[test]
+    fn decodes_compressed_stream() {
+        // x = +8 steps, y = -16 steps, z = 0 steps.
+        let element = (16 + 8) | (16 << 10);
+        let mut stream = Vec::new();
+
+        for value in [2, 0] {
+            stream.write_i32::<LittleEndian>(value).unwrap();
+        }
+
+        stream.write_f32::<LittleEndian>(1.0).unwrap();
+        stream.write_i32::<LittleEndian>(3).unwrap();
+        stream.write_u16::<LittleEndian>(0).unwrap();
+
+        // No bounds, linear interpolation, no times.
+        for value in [0, 0, 0] {
+            stream.write_i32::<LittleEndian>(value).unwrap();
+        }
+
+        // A key frame of two vertices without normals.
+        stream.write_i32::<LittleEndian>(1).unwrap();
+        stream.write_u16::<LittleEndian>(2).unwrap();
+
+        for value in [0.0, 0.0, 0.0, 1.0, 1.0, 1.0] {
+            stream.write_f32::<LittleEndian>(value).unwrap();
+        }
+
+        stream.write_u16::<LittleEndian>(0).unwrap();
+
+        // Two compressed shapes moving the second vertex.
+        for _ in 0..2 {
+            stream.write_i32::<LittleEndian>(0).unwrap();
+
+            for value in [1, 1, element, 0] {
+                stream.write_u16::<LittleEndian>(value).unwrap();
+            }
+        }
+
+        // Linear vertex deltas spanning two units per axis, no normal deltas.
+        for value in [1, 1, 0] {
+            stream.write_i32::<LittleEndian>(value).unwrap();
+        }
+
+        for value in [2.0, 2.0, 2.0, 0.0, 0.0, 0.0] {
+            stream.write_f32::<LittleEndian>(value).unwrap();
+        }
+
+        let key = AnimationKey::decode(&mut stream.as_slice(), ()).unwrap();
+        let vertex_indices = key.shape_vertex_indices(2).unwrap();
+        let frames = key.shape_frames(&vertex_indices).unwrap();
+
+        assert_eq!(vertex_indices, vec![0, 1]);
+        assert_eq!(
+            frames
+                .iter()
+                .map(|frame| frame.positions[1])
+                .collect::<Vec<_>>(),
+            vec![
+                Vector3::new(1.0, 1.0, 1.0),
+                Vector3::new(2.0, -1.0, 1.0),
+                Vector3::new(3.0, -3.0, 1.0),
+            ]
+        );
+        assert!(frames
+            .iter()
+            .all(|frame| frame.positions[0] == Vector3::default()));
+
+        let mut without_key_frame = key.clone();
+
+        if let AnimationKeys::Shapes(shapes) = &mut without_key_frame.keys {
+            shapes.remove(0);
+        }
+
+        assert_eq!(
+            without_key_frame.shape_frames(&vertex_indices),
+            Err(ShapeError::MissingKeyFrame { shape_index: 0 })
+        );
+    }
+
+    #[test]
+    fn dequantizes_exponential_steps() {
+        let exponential = AdaptiveDifferentialPulseCodeModulationType::Exponential;
+
+        assert_eq!(exponential.dequantize(0), 0.0);
+        assert_eq!(exponential.dequantize(-16), -1.0);
+        assert!(exponential.dequantize(8) < 0.5 * exponential.dequantize(15));
+    }
+
+    #[test]
+    fn deforms_animated_subset_of_vertices() {
+        let vertex = |x| Vertex {
+            vertex: Some(Vector3::new(x, 0.0, 0.0)),
+            ..Default::default()
+        };
+        let model_part = ModelPart {
+            vertices: vec![vertex(0.0), vertex(5.0), vertex(9.0)],
+            ..Default::default()
+        };
+        // Moves vertices 0 and 2 up by 8 linear steps of a 4 unit range, leaving vertex 1 alone.
+        let element = 16 | ((16 + 8) << 5) | (16 << 10);
+        let not_key_frame = |indices: Vec<u16>| Shape::NotKeyFrame {
+            animated_vertices: NotKeyFrameAnimatedVertices {
+                elements: vec![element; indices.len()],
+                indices,
+            },
+            normals: NotKeyFrameNormals {
+                indices: Vec::new(),
+                elements: Vec::new(),
+            },
+        };
+        let mut key = AnimationKey {
+            type_: 2,
+            target_hash: 7,
+            time_step: 1.0,
+            key_count: 2,
+            material_block_index: 1,
+            bounding_box_maximum: None,
+            interpolation_type: Interpolation::Linear,
+            times: None,
+            keys: AnimationKeys::Shapes(vec![
+                Shape::KeyFrame {
+                    animated_vertices: KeyFrameAnimatedVertices {
+                        elements: vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(9.0, 0.0, 0.0)],
+                    },
+                    normals: KeyFrameNormals {
+                        elements: Vec::new(),
+                    },
+                },
+                not_key_frame(vec![2, 0]),
+            ]),
+            adaptive_differential_pulse_code_modulation: Some(
+                AdaptiveDifferentialPulseCodeModulation {
+                    vertex_type: AdaptiveDifferentialPulseCodeModulationType::Linear,
+                    normal_type: AdaptiveDifferentialPulseCodeModulationType::None,
+                    vertex_range: Vector3::new(4.0, 4.0, 4.0),
+                    normal_range: Vector3::default(),
+                },
+            ),
+        };
+
+        let track = ShapeTrack::new(&key, &model_part).unwrap();
+        let deformed = track.deform(&model_part, 0.25, WrapMode::Clamp);
+
+        assert!(key.is_shape_track_for(7, 1));
+        assert_eq!(track.vertex_indices, vec![0, 2]);
+        assert_eq!(
+            deformed.positions,
+            vec![
+                Vector3::new(0.0, 0.5, 0.0),
+                Vector3::new(5.0, 0.0, 0.0),
+                Vector3::new(9.0, 0.5, 0.0)
+            ]
+        );
+        assert!(deformed.normals.is_empty());
+        assert_eq!(
+            deformed.bounds.unwrap().supremum,
+            Vector3::new(9.0, 0.5, 0.0)
+        );
+
+        if let AnimationKeys::Shapes(shapes) = &mut key.keys {
+            shapes[1] = not_key_frame(vec![0, 1, 2]);
+        }
+
+        assert_eq!(
+            ShapeTrack::new(&key, &model_part).unwrap_err(),
+            ShapeError::UnboundVertices {
+                animated_count: 2,
+                index_count: 3
+            }
+        );
+    }
+
+    mod animations {
+        use crate::{
+            mesh_parts, AnimationKey, AnimationKeys, Bsp, Chunk, Decode, ModelPart, ShapeTrack,
+        };
+        use std::{fs::File, io::BufReader};
+        use test_case::test_case;
+
+        fn decode(path: String) -> Bsp {
+            Bsp::decode(&mut BufReader::new(File::open(path).unwrap()), ()).unwrap()
+        }
+
+        /// The shape tracks of a ghost's animations, with the parts of its model they animate.
+        fn shape_tracks(ghost: &str) -> Vec<(AnimationKey, Vec<ModelPart>)> {
+            let model = decode(format!("assets/ghosts/{}.bsp", ghost));
+            let animations = decode(format!("assets/ghosts/animations/{}_anims.bsp", ghost));
+            let mesh_parts = mesh_parts(&model.chunks);
+
+            animations
+                .chunks
+                .into_iter()
+                .filter_map(|chunk| match chunk {
+                    Chunk::AnimationKey(key) if matches!(key.keys, AnimationKeys::Shapes(_)) => {
+                        let model_parts = mesh_parts
+                            .iter()
+                            .filter(|mesh_part| mesh_part.is_animated_by(&key))
+                            .map(|mesh_part| mesh_part.model_part.clone())
+                            .collect();
+
+                        Some((key, model_parts))
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// Checks how shape tracks name and bind to their part against real ghosts: every shape
+        /// track animates exactly one part of the model, and its indices bind it to that part.
+        #[test_case("aether" ; "aether")]
+        #[test_case("boo" ; "boo")]
+        #[test_case("moonscream" ; "moonscream")]
+        fn matches_assumed_targets(ghost: &str) {
+            for (key, model_parts) in shape_tracks(ghost) {
+                assert_eq!(model_parts.len(), 1, "{key:?}");
+                ShapeTrack::new(&key, &model_parts[0]).unwrap();
+            }
+        }
+
+        /// Checks the assumed packing of compressed shapes against real ghosts: the rebuilt
+        /// positions of every shape track stay within the track's bounds.
+        #[test_case("aether" ; "aether")]
+        #[test_case("boo" ; "boo")]
+        #[test_case("moonscream" ; "moonscream")]
+        fn matches_assumed_compression(ghost: &str) {
+            for (key, model_parts) in shape_tracks(ghost) {
+                let track = ShapeTrack::new(&key, &model_parts[0]).unwrap();
+                let Some(bounds) = &key.bounding_box_maximum else {
+                    continue;
+                };
+                let extent = bounds.supremum - bounds.infimum;
+                let tolerance = extent.x.max(extent.y).max(extent.z) * 0.01;
+
+                for position in track.frames.iter().flat_map(|frame| &frame.positions) {
+                    assert!(
+                        position.x >= bounds.infimum.x - tolerance
+                            && position.y >= bounds.infimum.y - tolerance
+                            && position.z >= bounds.infimum.z - tolerance
+                            && position.x <= bounds.supremum.x + tolerance
+                            && position.y <= bounds.supremum.y + tolerance
+                            && position.z <= bounds.supremum.z + tolerance,
+                        "{position:?} outside {bounds:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Convex polygon utilities for `NGonList`.
+//!
+//! The edge plane of vertex `i` is assumed to contain the edge from vertex `i` to the next one
+//! and to be perpendicular to the face. Queries do not rely on which side of the polygon edge
+//! planes face: the inside is the side the centroid of the polygon is on. `recompute_planes`
+//! makes them face out of the polygon, with faces wound counterclockwise around their face plane
+//! normal.
+
+use crate::{NGonFace, NGonList, NGonVertex, Plane, Vector3};
+
+/// A face of an `NGonList` together with its vertices.
+#[derive(Clone, Copy, Debug)]
+pub struct NGonPolygon<'a> {
+    pub face: &'a NGonFace,
+    pub vertices: &'a [NGonVertex],
+}
+
+impl NGonList {
+    /// The vertices of `face`, clamped to the vertices of the list.
+    pub fn face_vertices(&self, face: &NGonFace) -> &[NGonVertex] {
+        let start = (face.vertex_index as usize).min(self.vertices.len());
+        let end = (start + face.vertex_count as usize).min(self.vertices.len());
+
+        &self.vertices[start..end]
+    }
+
+    pub fn polygons(&self) -> impl Iterator<Item = NGonPolygon<'_>> {
+        self.faces.iter().map(|face| NGonPolygon {
+            face,
+            vertices: self.face_vertices(face),
+        })
+    }
+
+    /// Fans every face into triangles of indices into `vertices`, for export.
+    pub fn triangles(&self) -> Vec<[u32; 3]> {
+        self.faces
+            .iter()
+            .flat_map(|face| {
+                let first = face.vertex_index;
+                let count = self.face_vertices(face).len() as u32;
+
+                (1..count.saturating_sub(1))
+                    .map(move |corner| [first, first + corner, first + corner + 1])
+            })
+            .collect()
+    }
+
+    /// Recomputes the face and edge planes of every face from its vertices, so that they stay
+    /// consistent after the vertices are edited. Vertices shared by several faces keep the edge
+    /// plane of the last face.
+    pub fn recompute_planes(&mut self) {
+        for face in &mut self.faces {
+            let start = (face.vertex_index as usize).min(self.vertices.len());
+            let end = (start + face.vertex_count as usize).min(self.vertices.len());
+            let vertices = &mut self.vertices[start..end];
+            let positions = vertices
+                .iter()
+                .map(|vertex| vertex.vector)
+                .collect::<Vec<_>>();
+
+            // Newell's method, which is robust to nearly collinear corners.
+            let normal = positions
+                .iter()
+                .zip(positions.iter().cycle().skip(1))
+                .fold(Vector3::default(), |normal, (a, b)| {
+                    normal
+                        + Vector3::new(
+                            (a.y - b.y) * (a.z + b.z),
+                            (a.z - b.z) * (a.x + b.x),
+                            (a.x - b.x) * (a.y + b.y),
+                        )
+                })
+                .normalized();
+            let centroid = positions
+                .iter()
+                .fold(Vector3::default(), |sum, position| sum + *position)
+                * (1.0 / positions.len().max(1) as f32);
+
+            face.face_plane = plane(normal, &centroid);
+
+            for (index, vertex) in vertices.iter_mut().enumerate() {
+                let next = positions[(index + 1) % positions.len()];
+                let edge_normal = (next - positions[index]).cross(&normal).normalized();
+
+                vertex.edge_plane = plane(edge_normal, &positions[index]);
+            }
+        }
+    }
+}
+
+impl NGonPolygon<'_> {
+    pub fn positions(&self) -> impl Iterator<Item = Vector3> + '_ {
+        self.vertices.iter().map(|vertex| vertex.vector)
+    }
+
+    /// Whether `point`, assumed to lie in the plane of the face, is inside the polygon or on
+    /// its border.
+    pub fn contains(&self, point: &Vector3) -> bool {
+        if self.vertices.len() < 3 {
+            return false;
+        }
+
+        let centroid = self
+            .positions()
+            .fold(Vector3::default(), |sum, position| sum + position)
+            * (1.0 / self.vertices.len() as f32);
+
+        self.vertices.iter().all(|vertex| {
+            vertex.edge_plane.distance(point) * vertex.edge_plane.distance(&centroid) >= 0.0
+        })
+    }
+
+    /// Where the ray from `origin` along `direction` crosses the polygon, from either side, as
+    /// the multiple of `direction` to reach it. Rays parallel to the face never hit it.
+    pub fn intersect_ray(&self, origin: &Vector3, direction: &Vector3) -> Option<f32> {
+        let plane = &self.face.face_plane;
+        let speed = plane.normal().dot(direction);
+
+        if speed == 0.0 {
+            return None;
+        }
+
+        let t = -plane.distance(origin) / speed;
+
+        (t >= 0.0 && self.contains(&(*origin + *direction * t))).then_some(t)
+    }
+
+    /// Whether the polygon crosses the segment from `start` to `end`.
+    pub fn intersects_segment(&self, start: &Vector3, end: &Vector3) -> bool {
+        self.intersect_ray(start, &(*end - *start))
+            .is_some_and(|t| t <= 1.0)
+    }
+}
+
+fn plane(normal: Vector3, point: &Vector3) -> Plane {
+    Plane {
+        a: normal.x,
+        b: normal.y,
+        c: normal.z,
+        d: -normal.dot(point),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recomputes_planes_and_intersects() {
+        let vertex = |x, z| NGonVertex {
+            vector: Vector3::new(x, 0.0, z),
+            edge_plane: Plane::default(),
+        };
+        let mut list = NGonList {
+            vertices: vec![
+                vertex(0.0, 0.0),
+                vertex(0.0, 2.0),
+                vertex(2.0, 2.0),
+                vertex(2.0, 0.0),
+            ],
+            faces: vec![NGonFace {
+                face_plane: Plane::default(),
+                vertex_index: 0,
+                vertex_count: 4,
+                flags: 0,
+                test_count: 0,
+            }],
+        };
+
+        list.recompute_planes();
+
+        let polygon = list.polygons().next().unwrap();
+        let down = Vector3::new(0.0, -1.0, 0.0);
+
+        assert_eq!(
+            polygon.face.face_plane.normal(),
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+        assert!(polygon.contains(&Vector3::new(1.0, 0.0, 1.0)));
+        assert!(!polygon.contains(&Vector3::new(3.0, 0.0, 1.0)));
+        assert_eq!(
+            polygon.intersect_ray(&Vector3::new(1.0, 4.0, 1.0), &down),
+            Some(4.0)
+        );
+        assert_eq!(
+            polygon.intersect_ray(&Vector3::new(3.0, 4.0, 1.0), &down),
+            None
+        );
+        assert_eq!(list.triangles(), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn contains_with_inward_edge_planes() {
+        // The unit square in the XZ plane with edge planes written by hand, facing into it.
+        let vertex = |x, z, a, c, d| NGonVertex {
+            vector: Vector3::new(x, 0.0, z),
+            edge_plane: Plane { a, b: 0.0, c, d },
+        };
+        let vertices = [
+            vertex(0.0, 0.0, 0.0, 1.0, 0.0),
+            vertex(1.0, 0.0, -1.0, 0.0, 1.0),
+            vertex(1.0, 1.0, 0.0, -1.0, 1.0),
+            vertex(0.0, 1.0, 1.0, 0.0, 0.0),
+        ];
+        let face = NGonFace {
+            face_plane: Plane {
+                a: 0.0,
+                b: 1.0,
+                c: 0.0,
+                d: 0.0,
+            },
+            vertex_index: 0,
+            vertex_count: 4,
+            flags: 0,
+            test_count: 0,
+        };
+        let polygon = NGonPolygon {
+            face: &face,
+            vertices: &vertices,
+        };
+
+        assert!(polygon.contains(&Vector3::new(0.5, 0.0, 0.5)));
+        assert!(!polygon.contains(&Vector3::new(1.5, 0.0, 0.5)));
+        assert!(!polygon.contains(&Vector3::new(0.5, 0.0, -0.5)));
+    }
+
+    mod levels {
+        use crate::{bsp::decode_level, Chunk};
+        use test_case::test_case;
+
+        /// Checks the decoded occluders of real levels against the module documentation: every
+        /// vertex lies on its face plane, and the edge plane of each vertex contains its edge.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_planes(asset: &str) {
+            for chunk in &decode_level(asset).chunks {
+                let Chunk::OcclusionMesh(list) = chunk else {
+                    continue;
+                };
+
+                for polygon in list.polygons() {
+                    let tolerance = |position: &crate::Vector3| 1e-3 * position.length().max(1.0);
+
+                    for (index, vertex) in polygon.vertices.iter().enumerate() {
+                        let next = &polygon.vertices[(index + 1) % polygon.vertices.len()];
+
+                        for position in [&vertex.vector, &next.vector] {
+                            assert!(
+                                polygon.face.face_plane.distance(position).abs()
+                                    <= tolerance(position)
+                            );
+                            assert!(
+                                vertex.edge_plane.distance(position).abs() <= tolerance(position)
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,390 @@
+//! Queries on the occlusion BSP of a level.
+//!
+//! Each link of a branch is assumed to name a branch, or a leaf if the matching `*_leaf` field
+//! is set. Plane BSPs store a single signed link per side in the `*_leaf` fields: positive links
+//! name a branch, negative links name leaf `-1 - link`, and zero, the root, leads to empty space.
+//! Links out of range lead to empty space as well. An `OcclusionLeaf` holds the index of its
+//! occluder in the `OcclusionMesh` of the tree. `matches_assumed_layout` checks both encodings
+//! against real levels.
+
+use crate::{
+    BoundingBox, Bsp, Chunk, NGonFace, NGonList, NGonPolygon, Occlusion, OcclusionBranch, Vector3,
+};
+
+/// A node of an occlusion BSP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OcclusionNode {
+    Branch(usize),
+    Leaf(usize),
+    Empty,
+}
+
+/// The occlusion BSP of a floor and the occluders its leaves refer to.
+#[derive(Clone, Copy, Debug)]
+pub struct OcclusionTree<'a> {
+    pub occlusion: &'a Occlusion,
+    pub mesh: Option<&'a NGonList>,
+}
+
+impl Occlusion {
+    pub fn root(&self) -> OcclusionNode {
+        if self.branches.is_empty() {
+            OcclusionNode::Empty
+        } else {
+            OcclusionNode::Branch(0)
+        }
+    }
+
+    /// The nodes behind and in front of the plane of `branch`.
+    pub fn children(&self, branch: &OcclusionBranch) -> (OcclusionNode, OcclusionNode) {
+        if self.is_plane_bsp {
+            (
+                self.plane_node(branch.negative_leaf),
+                self.plane_node(branch.positive_leaf),
+            )
+        } else {
+            (
+                self.node(branch.negative_leaf != 0, branch.negative),
+                self.node(branch.positive_leaf != 0, branch.positive),
+            )
+        }
+    }
+
+    /// The leaf, or empty space, containing `point`. Points on a plane count as in front of it.
+    pub fn classify(&self, point: &Vector3) -> OcclusionNode {
+        let mut node = self.root();
+
+        // Bounded so that malformed links cannot loop forever.
+        for _ in 0..=self.branches.len() {
+            let OcclusionNode::Branch(index) = node else {
+                return node;
+            };
+            let branch = &self.branches[index];
+            let (negative, positive) = self.children(branch);
+
+            node = if branch.plane.distance(point) >= 0.0 {
+                positive
+            } else {
+                negative
+            };
+        }
+
+        OcclusionNode::Empty
+    }
+
+    /// The leaves ordered from the nearest to the furthest as seen from `viewpoint`.
+    pub fn front_to_back(&self, viewpoint: &Vector3) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        let mut visited = vec![false; self.branches.len()];
+        let mut stack = vec![self.root()];
+
+        while let Some(node) = stack.pop() {
+            match node {
+                OcclusionNode::Branch(index) => {
+                    if std::mem::replace(&mut visited[index], true) {
+                        continue;
+                    }
+
+                    let branch = &self.branches[index];
+                    let (negative, positive) = self.children(branch);
+
+                    if branch.plane.distance(viewpoint) >= 0.0 {
+                        stack.extend([negative, positive]);
+                    } else {
+                        stack.extend([positive, negative]);
+                    }
+                }
+                OcclusionNode::Leaf(index) => leaves.push(index),
+                OcclusionNode::Empty => (),
+            }
+        }
+
+        leaves
+    }
+
+    fn node(&self, is_leaf: bool, index: u32) -> OcclusionNode {
+        let index = index as usize;
+
+        if is_leaf && index < self.leaves.len() {
+            OcclusionNode::Leaf(index)
+        } else if !is_leaf && index < self.branches.len() {
+            OcclusionNode::Branch(index)
+        } else {
+            OcclusionNode::Empty
+        }
+    }
+
+    fn plane_node(&self, link: u32) -> OcclusionNode {
+        match link as i32 {
+            0 => OcclusionNode::Empty,
+            link if link < 0 => self.node(true, !link as u32),
+            link => self.node(false, link as u32),
+        }
+    }
+}
+
+impl<'a> OcclusionTree<'a> {
+    /// The tree selected by `World::floors[floor].occlusion_bsp`, which is assumed to index the
+    /// `Occlusion` chunks of `bsp` and the `OcclusionMesh` chunks alongside them.
+    pub fn for_floor(bsp: &'a Bsp, floor: usize) -> Option<Self> {
+        let index = bsp
+            .chunks
+            .iter()
+            .find_map(|chunk| match chunk {
+                Chunk::World(world) => world.floors.get(floor),
+                _ => None,
+            })?
+            .occlusion_bsp as usize;
+        let occlusion = bsp
+            .chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                Chunk::Occlusion(occlusion) => Some(occlusion),
+                _ => None,
+            })
+            .nth(index)?;
+        let mesh = bsp
+            .chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                Chunk::OcclusionMesh(mesh) => Some(mesh),
+                _ => None,
+            })
+            .nth(index);
+
+        Some(Self { occlusion, mesh })
+    }
+
+    /// The occluders ordered from the nearest to the furthest as seen from `viewpoint`. Trees
+    /// without leaves order nothing, so every face of the mesh is returned as is.
+    pub fn occluders(&self, viewpoint: &Vector3) -> Vec<&'a NGonFace> {
+        let Some(mesh) = self.mesh else {
+            return Vec::new();
+        };
+
+        if self.occlusion.leaves.is_empty() {
+            return mesh.faces.iter().collect();
+        }
+
+        self.occlusion
+            .front_to_back(viewpoint)
+            .into_iter()
+            .filter_map(|leaf| mesh.faces.get(self.occlusion.leaves[leaf].faces as usize))
+            .collect()
+    }
+
+    /// Whether `bounds` is entirely hidden from `viewpoint` behind a single occluder.
+    ///
+    /// Occluders are not combined: a box hidden only by several occluders together, such as
+    /// two walls meeting behind it, counts as visible. This errs on the side of drawing too
+    /// much.
+    pub fn is_hidden(&self, bounds: &BoundingBox, viewpoint: &Vector3) -> bool {
+        let Some(mesh) = self.mesh else {
+            return false;
+        };
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|corner: u8| {
+            let select = |bit: u8, infimum: f32, supremum: f32| {
+                if corner & (1 << bit) == 0 {
+                    infimum
+                } else {
+                    supremum
+                }
+            };
+
+            Vector3::new(
+                select(0, bounds.infimum.x, bounds.supremum.x),
+                select(1, bounds.infimum.y, bounds.supremum.y),
+                select(2, bounds.infimum.z, bounds.supremum.z),
+            )
+        });
+
+        // The shadow of a convex occluder is convex, so the box is hidden if its corners are.
+        self.occluders(viewpoint).into_iter().any(|face| {
+            let polygon = NGonPolygon {
+                face,
+                vertices: mesh.face_vertices(face),
+            };
+
+            corners
+                .iter()
+                .all(|corner| polygon.intersects_segment(viewpoint, corner))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NGonVertex, OcclusionLeaf, Plane};
+    use test_case::test_case;
+
+    fn plane(a: f32, d: f32) -> Plane {
+        Plane {
+            a,
+            b: 0.0,
+            c: 0.0,
+            d,
+        }
+    }
+
+    /// An x = 0 split with a 2 × 2 occluder at x = -1 behind it and a small one at x = 1 in
+    /// front of it, off to the side.
+    fn tree(is_plane_bsp: bool) -> (Occlusion, NGonList) {
+        let square = |x: f32, minimum: f32, maximum: f32| {
+            [
+                (minimum, minimum),
+                (maximum, minimum),
+                (maximum, maximum),
+                (minimum, maximum),
+            ]
+            .map(|(y, z)| NGonVertex {
+                vector: Vector3::new(x, y, z),
+                edge_plane: Plane::default(),
+            })
+        };
+        let face = |vertex_index| NGonFace {
+            face_plane: Plane::default(),
+            vertex_index,
+            vertex_count: 4,
+            flags: 0,
+            test_count: 0,
+        };
+        let branch = if is_plane_bsp {
+            OcclusionBranch {
+                plane: plane(1.0, 0.0),
+                negative_leaf: !0,
+                negative: 0,
+                positive_leaf: !1,
+                positive: 0,
+            }
+        } else {
+            OcclusionBranch {
+                plane: plane(1.0, 0.0),
+                negative_leaf: 1,
+                negative: 0,
+                positive_leaf: 1,
+                positive: 1,
+            }
+        };
+        let occlusion = Occlusion {
+            is_plane_bsp,
+            branches: vec![branch],
+            leaves: vec![OcclusionLeaf { faces: 0 }, OcclusionLeaf { faces: 1 }],
+            has_occlusion_meshes: true,
+        };
+        let mut mesh = NGonList {
+            vertices: [square(-1.0, -1.0, 1.0), square(1.0, 3.0, 4.0)].concat(),
+            faces: vec![face(0), face(4)],
+        };
+
+        mesh.recompute_planes();
+
+        (occlusion, mesh)
+    }
+
+    #[test_case(false ; "branch links")]
+    #[test_case(true ; "plane links")]
+    fn classifies_and_orders_leaves(is_plane_bsp: bool) {
+        let (occlusion, _) = tree(is_plane_bsp);
+
+        assert_eq!(
+            occlusion.classify(&Vector3::new(-0.5, 0.0, 0.0)),
+            OcclusionNode::Leaf(0)
+        );
+        assert_eq!(
+            occlusion.front_to_back(&Vector3::new(5.0, 0.0, 0.0)),
+            vec![1, 0]
+        );
+        assert_eq!(
+            occlusion.front_to_back(&Vector3::new(-5.0, 0.0, 0.0)),
+            vec![0, 1]
+        );
+    }
+
+    #[test_case(false ; "branch links")]
+    #[test_case(true ; "plane links")]
+    fn hides_boxes_behind_occluders(is_plane_bsp: bool) {
+        let (occlusion, mesh) = tree(is_plane_bsp);
+        let tree = OcclusionTree {
+            occlusion: &occlusion,
+            mesh: Some(&mesh),
+        };
+        let viewpoint = Vector3::new(5.0, 0.0, 0.0);
+        let cube = |y: f32| BoundingBox {
+            infimum: Vector3::new(-3.1, y - 0.1, -0.1),
+            supremum: Vector3::new(-2.9, y + 0.1, 0.1),
+        };
+
+        assert!(tree.is_hidden(&cube(0.0), &viewpoint));
+        assert!(!tree.is_hidden(&cube(3.0), &viewpoint));
+        assert!(!tree.is_hidden(&cube(0.0), &Vector3::new(-5.0, 0.0, 0.0)));
+    }
+
+    mod levels {
+        use crate::{bsp::decode_level, Chunk, OcclusionTree};
+        use test_case::test_case;
+
+        /// Checks decoded trees against the layout in the module documentation: leaves name
+        /// faces of the mesh alongside the tree, links name existing branches or leaves, and
+        /// every leaf is reached.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_layout(asset: &str) {
+            let bsp = decode_level(asset);
+            let floor_count = bsp
+                .chunks
+                .iter()
+                .find_map(|chunk| match chunk {
+                    Chunk::World(world) => Some(world.floors.len()),
+                    _ => None,
+                })
+                .unwrap_or(0);
+
+            for floor in 0..floor_count {
+                let Some(tree) = OcclusionTree::for_floor(&bsp, floor) else {
+                    continue;
+                };
+                let occlusion = tree.occlusion;
+                let mut reached = vec![false; occlusion.leaves.len()];
+
+                if let Some(mesh) = tree.mesh {
+                    assert!(occlusion
+                        .leaves
+                        .iter()
+                        .all(|leaf| (leaf.faces as usize) < mesh.faces.len()));
+                }
+
+                for branch in &occlusion.branches {
+                    let links = if occlusion.is_plane_bsp {
+                        [branch.negative_leaf, branch.positive_leaf].map(|link| {
+                            let link = link as i32;
+
+                            (link < 0, if link < 0 { !link } else { link })
+                        })
+                    } else {
+                        [
+                            (branch.negative_leaf != 0, branch.negative as i32),
+                            (branch.positive_leaf != 0, branch.positive as i32),
+                        ]
+                    };
+
+                    for (is_leaf, index) in links {
+                        let index = index as usize;
+
+                        if is_leaf {
+                            assert!(index < occlusion.leaves.len());
+
+                            reached[index] = true;
+                        } else {
+                            assert!(index < occlusion.branches.len());
+                        }
+                    }
+                }
+
+                assert!(reached.iter().all(|reached| *reached));
+            }
+        }
+    }
+}
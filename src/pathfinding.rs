@@ -0,0 +1,346 @@
+//! A* pathfinding over the waypoint graph of a navigation mesh.
+//!
+//! The start position snaps to its nearest allowed waypoint, and the goal to the nearest one
+//! reachable from there. The route between them is then shortened by skipping every waypoint the
+//! caller reports can be cut across.
+
+use crate::{Vector3, Waypoint, WaypointGraph};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, VecDeque},
+};
+
+/// Restricts the waypoints and links a path may use. A flag set matches if it contains every
+/// required flag and none of the excluded ones, so the default allows everything.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathFilter {
+    pub required_waypoint_flags: u32,
+    pub excluded_waypoint_flags: u32,
+    pub required_link_flags: u32,
+    pub excluded_link_flags: u32,
+}
+
+/// A route through the waypoint graph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    /// The waypoints visited, in order.
+    pub waypoints: Vec<usize>,
+    /// The start position, the positions of the waypoints that cannot be cut across and the goal
+    /// position.
+    pub points: Vec<Vector3>,
+}
+
+/// An entry of the A* open set, ordered so that the lowest estimate pops first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candidate {
+    estimate: f32,
+    waypoint: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(other.waypoint.cmp(&self.waypoint))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PathFilter {
+    pub fn allows_waypoint(&self, waypoint: &Waypoint) -> bool {
+        matches(
+            waypoint.flags,
+            self.required_waypoint_flags,
+            self.excluded_waypoint_flags,
+        )
+    }
+
+    pub fn allows_link(&self, flags: u32) -> bool {
+        matches(flags, self.required_link_flags, self.excluded_link_flags)
+    }
+}
+
+impl WaypointGraph<'_> {
+    /// The allowed waypoint closest to `position`.
+    pub fn nearest_waypoint(&self, position: &Vector3, filter: &PathFilter) -> Option<usize> {
+        self.nearest_waypoint_where(position, |index| self.is_allowed(index, filter))
+    }
+
+    fn nearest_waypoint_where(
+        &self,
+        position: &Vector3,
+        predicate: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        self.navigation_mesh
+            .waypoints
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| predicate(*index))
+            .min_by(|(_, a), (_, b)| {
+                (a.position - *position)
+                    .length()
+                    .total_cmp(&(b.position - *position).length())
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// The waypoints reachable from `start` over allowed links, `start` included.
+    pub fn reachable_waypoints(&self, start: usize, filter: &PathFilter) -> Vec<bool> {
+        let mut reachable = vec![false; self.navigation_mesh.waypoints.len()];
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(waypoint) = queue.pop_front() {
+            if !self.is_allowed(waypoint, filter)
+                || std::mem::replace(&mut reachable[waypoint], true)
+            {
+                continue;
+            }
+
+            queue.extend(
+                self.neighbours(waypoint)
+                    .filter(|(_, flags)| filter.allows_link(*flags))
+                    .map(|(target, _)| target),
+            );
+        }
+
+        reachable
+    }
+
+    /// Finds the shortest route from `start` to `goal` with A*, then smooths it.
+    ///
+    /// `start` snaps to its nearest allowed waypoint and `goal` to the nearest waypoint reachable
+    /// from it, so a goal on an island is approached as closely as the graph allows. No path is
+    /// returned only if no waypoint is allowed. `is_clear` tells whether a straight move between
+    /// two points is possible, for instance
+    /// `|a, b| collision_world.cast_segment(a, b).is_none()`.
+    pub fn find_path(
+        &self,
+        start: &Vector3,
+        goal: &Vector3,
+        filter: &PathFilter,
+        is_clear: impl Fn(&Vector3, &Vector3) -> bool,
+    ) -> Option<Path> {
+        let first = self.nearest_waypoint(start, filter)?;
+        let reachable = self.reachable_waypoints(first, filter);
+        let last = self.nearest_waypoint_where(goal, |index| reachable[index])?;
+        let position = |waypoint: usize| self.navigation_mesh.waypoints[waypoint].position;
+        let goal_position = position(last);
+        let waypoint_count = self.navigation_mesh.waypoints.len();
+
+        let mut costs = vec![f32::INFINITY; waypoint_count];
+        let mut previous = vec![None; waypoint_count];
+        let mut open = BinaryHeap::from([Candidate {
+            estimate: (goal_position - position(first)).length(),
+            waypoint: first,
+        }]);
+
+        costs[first] = 0.0;
+
+        while let Some(Candidate { waypoint, .. }) = open.pop() {
+            if waypoint == last {
+                break;
+            }
+
+            for (target, flags) in self.neighbours(waypoint) {
+                if !filter.allows_link(flags) || !self.is_allowed(target, filter) {
+                    continue;
+                }
+
+                let cost = costs[waypoint] + (position(target) - position(waypoint)).length();
+
+                if cost < costs[target] {
+                    costs[target] = cost;
+                    previous[target] = Some(waypoint);
+                    open.push(Candidate {
+                        estimate: cost + (goal_position - position(target)).length(),
+                        waypoint: target,
+                    });
+                }
+            }
+        }
+
+        let mut waypoints = vec![last];
+
+        while let Some(waypoint) = previous[*waypoints.last().unwrap()] {
+            waypoints.push(waypoint);
+        }
+
+        waypoints.reverse();
+
+        let points = [*start]
+            .into_iter()
+            .chain(waypoints.iter().map(|waypoint| position(*waypoint)))
+            .chain([*goal])
+            .collect::<Vec<_>>();
+
+        Some(Path {
+            waypoints,
+            points: smooth(&points, is_clear),
+        })
+    }
+
+    fn is_allowed(&self, waypoint: usize, filter: &PathFilter) -> bool {
+        self.waypoint(waypoint)
+            .is_some_and(|waypoint| filter.allows_waypoint(waypoint))
+    }
+}
+
+impl Path {
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|segment| (segment[1] - segment[0]).length())
+            .sum()
+    }
+}
+
+/// Skips every point that can be cut across, keeping the first and last points.
+fn smooth(points: &[Vector3], is_clear: impl Fn(&Vector3, &Vector3) -> bool) -> Vec<Vector3> {
+    let mut smoothed = Vec::new();
+    let mut current = 0;
+
+    if points.is_empty() {
+        return smoothed;
+    }
+
+    smoothed.push(points[0]);
+
+    while current + 1 < points.len() {
+        let next = (current + 2..points.len())
+            .rev()
+            .find(|next| is_clear(&points[current], &points[*next]))
+            .unwrap_or(current + 1);
+
+        smoothed.push(points[next]);
+        current = next;
+    }
+
+    smoothed
+}
+
+fn matches(flags: u32, required: u32, excluded: u32) -> bool {
+    flags & required == required && flags & excluded == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, NavigationMesh};
+
+    /// A square of waypoints, 0 → 1 → 2 the long way round and 0 → 3 → 2 through a waypoint
+    /// flagged 1.
+    fn navigation_mesh() -> NavigationMesh {
+        let waypoint = |x, z, flags| Waypoint {
+            position: Vector3::new(x, 0.0, z),
+            flags,
+        };
+        let link = |waypoint_index| Link {
+            waypoint_index,
+            flags: 0,
+        };
+
+        NavigationMesh {
+            waypoints: vec![
+                waypoint(0.0, 0.0, 0),
+                waypoint(10.0, 0.0, 0),
+                waypoint(10.0, 10.0, 0),
+                waypoint(1.0, 5.0, 1),
+            ],
+            links: vec![
+                vec![link(1), link(3)],
+                vec![link(2)],
+                Vec::new(),
+                vec![link(2)],
+            ],
+        }
+    }
+
+    #[test]
+    fn finds_shortest_allowed_path() {
+        let navigation_mesh = navigation_mesh();
+        let graph = WaypointGraph::new(&navigation_mesh);
+        let start = Vector3::new(-1.0, 0.0, 0.0);
+        let goal = Vector3::new(11.0, 0.0, 11.0);
+
+        let path = graph
+            .find_path(&start, &goal, &PathFilter::default(), |_, _| false)
+            .unwrap();
+
+        assert_eq!(path.waypoints, vec![0, 3, 2]);
+        assert_eq!(path.points.len(), 5);
+        assert_eq!(
+            graph
+                .find_path(&start, &goal, &PathFilter::default(), |_, _| true)
+                .unwrap()
+                .points,
+            vec![start, goal]
+        );
+
+        let filter = PathFilter {
+            excluded_waypoint_flags: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            graph
+                .find_path(&start, &goal, &filter, |_, _| false)
+                .unwrap()
+                .waypoints,
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn snaps_unreachable_goal_to_nearest_reachable_waypoint() {
+        let mut navigation_mesh = navigation_mesh();
+
+        // An island next to the goal, linked to nothing.
+        navigation_mesh.waypoints.push(Waypoint {
+            position: Vector3::new(11.0, 0.0, 12.0),
+            flags: 0,
+        });
+        navigation_mesh.links.push(Vec::new());
+
+        let graph = WaypointGraph::new(&navigation_mesh);
+        let goal = Vector3::new(11.0, 0.0, 11.5);
+
+        assert_eq!(
+            graph.nearest_waypoint(&goal, &PathFilter::default()),
+            Some(4)
+        );
+        assert_eq!(
+            graph
+                .find_path(
+                    &Vector3::new(-1.0, 0.0, 0.0),
+                    &goal,
+                    &PathFilter::default(),
+                    |_, _| false
+                )
+                .unwrap()
+                .waypoints,
+            vec![0, 3, 2]
+        );
+
+        // Links are one-way, so nothing leads back from waypoint 2 to waypoint 0.
+        assert_eq!(
+            graph
+                .find_path(
+                    &Vector3::new(10.0, 0.0, 10.0),
+                    &Vector3::new(-1.0, 0.0, 0.0),
+                    &PathFilter::default(),
+                    |_, _| true
+                )
+                .unwrap()
+                .waypoints,
+            vec![2]
+        );
+    }
+}
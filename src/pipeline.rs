@@ -0,0 +1,140 @@
+use crate::{BlendFactor, ComparisonFunction, Material, MaterialFlags};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendOperation {
+    Add,
+}
+
+/// `result = source * source_factor <operation> destination * destination_factor`, applied to
+/// color and alpha alike.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendState {
+    pub source_factor: BlendFactor,
+    pub destination_factor: BlendFactor,
+    pub operation: BlendOperation,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlphaTest {
+    pub function: ComparisonFunction,
+    /// Normalized to `[0, 1]`.
+    pub cutoff: f32,
+}
+
+/// The fixed function state a material is drawn with, independent of any graphics API.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PipelineState {
+    /// `None` when the material is opaque.
+    pub blend: Option<BlendState>,
+    pub depth_test: ComparisonFunction,
+    pub depth_write: bool,
+    pub color_write: bool,
+    pub cull: CullMode,
+    /// `None` when every fragment passes.
+    pub alpha_test: Option<AlphaTest>,
+}
+
+impl PipelineState {
+    /// Whether the material has to be drawn after the opaque geometry.
+    pub fn is_translucent(&self) -> bool {
+        self.blend.is_some()
+    }
+}
+
+impl Material {
+    /// Describes how the material is drawn.
+    ///
+    /// Back faces are culled unless the material is `TWO_SIDED`. The `Both*` blend factors set
+    /// both the source and destination factors, which is resolved here.
+    pub fn pipeline_state(&self) -> PipelineState {
+        let blend = self.blend.then(|| {
+            let (source_factor, destination_factor) = match self.blend_modes.source_mode {
+                BlendFactor::BothSourceAlpha => {
+                    (BlendFactor::SourceAlpha, BlendFactor::InverseSourceAlpha)
+                }
+                BlendFactor::BothInverseSourceAlpha => {
+                    (BlendFactor::InverseSourceAlpha, BlendFactor::SourceAlpha)
+                }
+                source_factor => (source_factor, self.blend_modes.destination_mode),
+            };
+
+            BlendState {
+                source_factor,
+                destination_factor,
+                operation: BlendOperation::Add,
+            }
+        });
+
+        let alpha_test = (self.alpha_test
+            && self.alpha_test_mode.comparision_function != ComparisonFunction::Always)
+            .then(|| AlphaTest {
+                function: self.alpha_test_mode.comparision_function,
+                cutoff: self.alpha_test_mode.normalized_reference(),
+            });
+
+        PipelineState {
+            blend,
+            depth_test: self.depth_buffer_comparison_mode,
+            depth_write: self.depth_buffer_write,
+            color_write: self.color_buffer_write != 0,
+            cull: if self.flags.contains(MaterialFlags::TWO_SIDED) {
+                CullMode::None
+            } else {
+                CullMode::Back
+            },
+            alpha_test,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AlphaTestMode, BlendModes};
+
+    #[test]
+    fn resolves_both_blend_factors_and_alpha_reference() {
+        let material = Material {
+            flags: MaterialFlags::TWO_SIDED,
+            blend: true,
+            blend_modes: BlendModes {
+                source_mode: BlendFactor::BothSourceAlpha,
+                destination_mode: BlendFactor::Zero,
+            },
+            alpha_test: true,
+            alpha_test_mode: AlphaTestMode {
+                comparision_function: ComparisonFunction::Greater,
+                reference: 51.0,
+            },
+            depth_buffer_write: false,
+            ..Default::default()
+        };
+
+        let pipeline_state = material.pipeline_state();
+
+        assert_eq!(
+            pipeline_state.blend,
+            Some(BlendState {
+                source_factor: BlendFactor::SourceAlpha,
+                destination_factor: BlendFactor::InverseSourceAlpha,
+                operation: BlendOperation::Add,
+            })
+        );
+        assert_eq!(
+            pipeline_state.alpha_test,
+            Some(AlphaTest {
+                function: ComparisonFunction::Greater,
+                cutoff: 0.2,
+            })
+        );
+        assert_eq!(pipeline_state.depth_test, ComparisonFunction::LessEqual);
+        assert!(!pipeline_state.depth_write);
+        assert_eq!(pipeline_state.cull, CullMode::None);
+    }
+}
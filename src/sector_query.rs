@@ -0,0 +1,338 @@
+//! Queries on the sector octree of a level.
+//!
+//! The octree is assumed to be laid out as follows: octant 0 is the root, a subtree's
+//! `subtree_index` is the first of its eight consecutive child octants and a leaf's `leaf_index`
+//! indexes `leaves`. The material blocks of a leaf are the `world_blocks_count` blocks from
+//! `world_block_index` on. `Zones::octant_connections` lists the zones of every leaf back to
+//! back, a leaf's zones being the `zone_count` entries from `zone` on.
+
+use crate::{
+    BoundingBox, Frustum, SectorOctree, SectorOctreeLeaf, SectorOctreeOctant, Vector3, Zones,
+};
+use std::collections::BTreeSet;
+
+const CHILD_COUNT: usize = 8;
+
+impl SectorOctreeOctant {
+    pub fn bounds(&self) -> &BoundingBox {
+        match self {
+            Self::Leaf { bounds, .. } | Self::Subtree { bounds, .. } => bounds,
+        }
+    }
+
+    pub fn flags(&self) -> u32 {
+        match self {
+            Self::Leaf { flags, .. } | Self::Subtree { flags, .. } => *flags,
+        }
+    }
+}
+
+impl SectorOctreeLeaf {
+    /// The zones of the leaf, skipping indices past the zones.
+    pub fn zones<'a>(&self, zones: &'a Zones) -> impl Iterator<Item = usize> + 'a {
+        zones
+            .octant_connections
+            .iter()
+            .skip(self.zone as usize)
+            .take(self.zone_count as usize)
+            .map(|zone| *zone as usize)
+            .filter(|zone| *zone < zones.zones.len())
+    }
+}
+
+impl SectorOctree {
+    /// The material blocks of the world mesh in `leaf`.
+    pub fn leaf_material_blocks<'a>(
+        &'a self,
+        leaf: &SectorOctreeLeaf,
+    ) -> impl Iterator<Item = u32> + 'a {
+        let (start, count) = match leaf.world_block_index {
+            Some(start) => (start as usize, leaf.world_blocks_count.max(0) as usize),
+            None => (0, 0),
+        };
+
+        self.blocks
+            .iter()
+            .skip(start)
+            .take(count)
+            .map(|block| block.material_block_index)
+    }
+
+    /// The leaf whose octant contains `point`.
+    pub fn leaf_at(&self, point: &Vector3) -> Option<&SectorOctreeLeaf> {
+        let mut octant = self.octants.first()?;
+
+        if !octant.bounds().contains(point) {
+            return None;
+        }
+
+        // Bounded so that malformed indices cannot loop forever.
+        for _ in 0..self.octants.len() {
+            match octant {
+                SectorOctreeOctant::Leaf { leaf_index, .. } => {
+                    return self.leaves.get(*leaf_index as usize)
+                }
+                SectorOctreeOctant::Subtree { subtree_index, .. } => {
+                    octant = self
+                        .children(*subtree_index)
+                        .iter()
+                        .find(|child| child.bounds().contains(point))?;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The leaves whose octants intersect `bounds`.
+    pub fn leaves_in_box(&self, bounds: &BoundingBox) -> Vec<&SectorOctreeLeaf> {
+        self.leaves_where(|octant| octant.intersects(bounds))
+    }
+
+    /// The leaves whose octants may be visible in `frustum`.
+    pub fn leaves_in_frustum(&self, frustum: &Frustum) -> Vec<&SectorOctreeLeaf> {
+        self.leaves_where(|octant| frustum.intersects(octant))
+    }
+
+    /// The material blocks in the leaves intersecting `bounds`, sorted and without duplicates.
+    pub fn material_blocks_in_box(&self, bounds: &BoundingBox) -> Vec<u32> {
+        self.material_blocks(self.leaves_in_box(bounds))
+    }
+
+    /// The material blocks in the leaves that may be visible in `frustum`, sorted and without
+    /// duplicates.
+    pub fn material_blocks_in_frustum(&self, frustum: &Frustum) -> Vec<u32> {
+        self.material_blocks(self.leaves_in_frustum(frustum))
+    }
+
+    /// The zones of the leaves intersecting `bounds`, sorted and without duplicates.
+    pub fn zones_in_box(&self, bounds: &BoundingBox, zones: &Zones) -> Vec<usize> {
+        self.leaves_in_box(bounds)
+            .into_iter()
+            .flat_map(|leaf| leaf.zones(zones))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn children(&self, subtree_index: u32) -> &[SectorOctreeOctant] {
+        let start = (subtree_index as usize).min(self.octants.len());
+        let end = (start + CHILD_COUNT).min(self.octants.len());
+
+        &self.octants[start..end]
+    }
+
+    fn material_blocks(&self, leaves: Vec<&SectorOctreeLeaf>) -> Vec<u32> {
+        leaves
+            .into_iter()
+            .flat_map(|leaf| self.leaf_material_blocks(leaf))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Collects the leaves below every octant whose bounds pass `predicate`, each octant being
+    /// visited at most once.
+    fn leaves_where(&self, predicate: impl Fn(&BoundingBox) -> bool) -> Vec<&SectorOctreeLeaf> {
+        let mut leaves = Vec::new();
+        let mut visited = vec![false; self.octants.len()];
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let Some(octant) = self.octants.get(index) else {
+                continue;
+            };
+
+            if std::mem::replace(&mut visited[index], true) || !predicate(octant.bounds()) {
+                continue;
+            }
+
+            match octant {
+                SectorOctreeOctant::Leaf { leaf_index, .. } => {
+                    leaves.extend(self.leaves.get(*leaf_index as usize))
+                }
+                SectorOctreeOctant::Subtree { subtree_index, .. } => {
+                    let start = *subtree_index as usize;
+
+                    stack.extend((start..start + CHILD_COUNT).rev());
+                }
+            }
+        }
+
+        leaves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Matrix, Plane, SectorOctreeBlock, Zone};
+
+    fn bounds(infimum: (f32, f32, f32), supremum: (f32, f32, f32)) -> BoundingBox {
+        BoundingBox {
+            infimum: Vector3::new(infimum.0, infimum.1, infimum.2),
+            supremum: Vector3::new(supremum.0, supremum.1, supremum.2),
+        }
+    }
+
+    /// A root over [0, 2]³ split into eight unit octants, leaf `n` holding block `n` and the
+    /// `n`th zone connection.
+    fn octree() -> SectorOctree {
+        let mut octants = vec![SectorOctreeOctant::Subtree {
+            bounds: bounds((0.0, 0.0, 0.0), (2.0, 2.0, 2.0)),
+            flags: 0,
+            subtree_index: 1,
+        }];
+        let mut leaves = Vec::new();
+
+        for child in 0..8u32 {
+            let offset = |bit: u32| ((child >> bit) & 1) as f32;
+            let infimum = (offset(0), offset(1), offset(2));
+
+            octants.push(SectorOctreeOctant::Leaf {
+                bounds: bounds(infimum, (infimum.0 + 1.0, infimum.1 + 1.0, infimum.2 + 1.0)),
+                flags: 0,
+                leaf_index: child,
+            });
+            leaves.push(SectorOctreeLeaf {
+                sector_floor_flag: 0,
+                world_blocks_count: 1,
+                world_block_index: Some(child),
+                zone_count: 1,
+                zone: child,
+            });
+        }
+
+        SectorOctree {
+            blocks: (0..8)
+                .map(|index| SectorOctreeBlock {
+                    material_block_index: index * 10,
+                })
+                .collect(),
+            leaves,
+            octants,
+        }
+    }
+
+    #[test]
+    fn finds_leaves_and_blocks() {
+        let octree = octree();
+
+        assert_eq!(
+            octree.leaf_at(&Vector3::new(1.5, 0.5, 0.5)).unwrap().zone,
+            1
+        );
+        assert!(octree.leaf_at(&Vector3::new(3.0, 0.5, 0.5)).is_none());
+        assert_eq!(
+            octree.material_blocks_in_box(&bounds((0.2, 0.2, 0.2), (0.8, 0.8, 1.5))),
+            vec![0, 40]
+        );
+        let zones = Zones {
+            octant_connections: (0..8).rev().collect(),
+            zones: (0..8)
+                .map(|_| Zone {
+                    bounding_box: bounds((0.0, 0.0, 0.0), (2.0, 2.0, 2.0)),
+                    hash: 0,
+                    ngon_index: 0,
+                    spline_index: 0,
+                    clump_index: 0,
+                    floor_flags: 0,
+                    zone_top: None,
+                })
+                .collect(),
+        };
+
+        assert_eq!(
+            octree.zones_in_box(&bounds((1.2, 1.2, 0.2), (1.8, 1.8, 0.8)), &zones),
+            vec![4]
+        );
+    }
+
+    #[test]
+    fn culls_octants_outside_frustum() {
+        let octree = octree();
+        let frustum = Frustum {
+            planes: vec![Plane {
+                a: -1.0,
+                b: 0.0,
+                c: 0.0,
+                d: 0.5,
+            }],
+        };
+
+        assert_eq!(
+            octree.material_blocks_in_frustum(&frustum),
+            vec![0, 20, 40, 60]
+        );
+
+        let frustum = Frustum::from_view_projection(&Matrix::identity());
+
+        assert!(frustum.contains(&Vector3::new(0.5, -0.5, 0.5)));
+        assert!(!frustum.contains(&Vector3::new(0.5, 0.5, -0.5)));
+    }
+
+    mod levels {
+        use super::super::CHILD_COUNT;
+        use crate::{bsp::decode_level, Chunk, SectorOctreeOctant};
+        use test_case::test_case;
+
+        /// Checks decoded octrees against the layout in the module documentation: children
+        /// exist and lie within their parent, and leaves name existing leaves, blocks and zone
+        /// connections.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_layout(asset: &str) {
+            let bsp = decode_level(asset);
+            let zones = bsp.chunks.iter().find_map(|chunk| match chunk {
+                Chunk::Zones(zones) => Some(zones),
+                _ => None,
+            });
+
+            for chunk in &bsp.chunks {
+                let Chunk::SectorOctree(octree) = chunk else {
+                    continue;
+                };
+
+                for octant in &octree.octants {
+                    match octant {
+                        SectorOctreeOctant::Leaf { leaf_index, .. } => {
+                            assert!((*leaf_index as usize) < octree.leaves.len())
+                        }
+                        SectorOctreeOctant::Subtree {
+                            bounds,
+                            subtree_index,
+                            ..
+                        } => {
+                            let children = octree.children(*subtree_index);
+
+                            assert_eq!(children.len(), CHILD_COUNT);
+                            assert!(children.iter().all(|child| {
+                                bounds.contains(&child.bounds().infimum)
+                                    && bounds.contains(&child.bounds().supremum)
+                            }));
+                        }
+                    }
+                }
+
+                for leaf in &octree.leaves {
+                    if let Some(start) = leaf.world_block_index {
+                        assert!(
+                            start as usize + leaf.world_blocks_count as usize
+                                <= octree.blocks.len()
+                        );
+                    }
+
+                    if let Some(zones) = zones {
+                        assert!(
+                            (leaf.zone + leaf.zone_count) as usize
+                                <= zones.octant_connections.len()
+                        );
+                        assert_eq!(leaf.zones(zones).count(), leaf.zone_count as usize);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{
+    AnimationClip, AnimationLibrary, Bone, Bsp, Chunk, Clump, Frame, Matrix, ModelPart,
+    TrackTargets, Vector3, WrapMode,
+};
+
+/// A frame of a skeleton and the index of its parent frame.
+#[derive(Clone, Debug)]
+pub struct SkeletonFrame<'a> {
+    pub frame: &'a Frame,
+    pub parent: Option<usize>,
+}
+
+/// The frame hierarchy of a ghost model and the bones its vertices are skinned to.
+#[derive(Clone, Debug)]
+pub struct Skeleton<'a> {
+    pub frames: Vec<SkeletonFrame<'a>>,
+    pub clump: &'a Clump,
+    /// The frame of each bone of `clump`, by bone index.
+    pub bone_frames: Vec<Option<usize>>,
+    targets: TrackTargets,
+}
+
+/// The `BoneObj` frames of `chunks` in order, each with the index of its parent: the last frame
+/// read at the stream depth of the preceding `LevelObj` chunk.
+pub fn frame_hierarchy(chunks: &[Chunk]) -> Vec<SkeletonFrame<'_>> {
+    let mut frames = Vec::new();
+    let mut stack: Vec<usize> = Vec::new();
+    let mut stream_depth = 0;
+
+    for chunk in chunks {
+        match chunk {
+            Chunk::LevelObj(frame_child) => stream_depth = frame_child.stream_depth as usize,
+            Chunk::BoneObj(frame) => {
+                stack.truncate(stream_depth);
+
+                frames.push(SkeletonFrame {
+                    frame,
+                    parent: stack.last().copied(),
+                });
+                stack.push(frames.len() - 1);
+            }
+            _ => (),
+        }
+    }
+
+    frames
+}
+
+/// The skinned vertex data of a model part.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkinnedVertices {
+    /// One position per vertex of the part.
+    pub positions: Vec<Vector3>,
+    /// One normal per vertex of the part, empty if the part has no normals.
+    pub normals: Vec<Vector3>,
+}
+
+impl<'a> Skeleton<'a> {
+    /// Builds the skeleton from the `BoneObj` frames, arranged by `frame_hierarchy` as the glTF
+    /// exporter does, and the first `SkinObj` clump of a model. Returns `None` if the model has
+    /// no clump.
+    pub fn new(model: &'a Bsp) -> Option<Self> {
+        let clump = model.chunks.iter().find_map(|chunk| match chunk {
+            Chunk::SkinObj(skin) => Some(skin),
+            _ => None,
+        })?;
+        let frames = frame_hierarchy(&model.chunks);
+        let bone_frames = (0..clump.bone_count.len())
+            .map(|bone_index| {
+                frames
+                    .iter()
+                    .position(|frame| frame.frame.bone_index == bone_index as i32)
+            })
+            .collect();
+
+        let targets = TrackTargets::new(
+            &frames.iter().map(|frame| frame.frame).collect::<Vec<_>>(),
+            Some(clump),
+        );
+
+        Some(Self {
+            frames,
+            clump,
+            bone_frames,
+            targets,
+        })
+    }
+
+    pub fn bones(&self) -> &'a [Bone] {
+        &self.clump.bone_count
+    }
+
+    /// Maps animation track targets to frame indices.
+    pub fn track_targets(&self) -> &TrackTargets {
+        &self.targets
+    }
+
+    /// The transform of every frame relative to its parent in the bind pose. Root frames, and
+    /// every frame of a clump without hierarchy, are relative to the model.
+    pub fn bind_pose(&self) -> Vec<Matrix> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                if frame.parent.is_some() && self.clump.has_hierarchy {
+                    frame.frame.local_transform_matrix.clone()
+                } else {
+                    frame.frame.global_transform_matrix.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// The bind pose with the tracks of `clip` applied at `time`. Animated bones that lack a
+    /// rotation or translation track take it from the base poses of `library`.
+    pub fn animated_pose(
+        &self,
+        library: &AnimationLibrary,
+        clip: &AnimationClip,
+        time: f32,
+        wrap_mode: WrapMode,
+    ) -> Vec<Matrix> {
+        let mut pose = self.bind_pose();
+
+        for target_hash in clip.bone_tracks().keys() {
+            let frame_index = match self.targets.get(*target_hash) {
+                Some(frame_index) => frame_index,
+                None => continue,
+            };
+            let bone_index = self.frames[frame_index].frame.bone_index.max(0) as usize;
+            let bone_pose = library.sample_bone(clip, *target_hash, bone_index, time, wrap_mode);
+            let (scale, _, _) = pose[frame_index].decompose();
+
+            pose[frame_index] = Matrix::from_scale_rotation_translation(
+                scale,
+                bone_pose.rotation,
+                bone_pose.translation,
+            );
+        }
+
+        pose
+    }
+
+    /// Concatenates a pose, as returned by `bind_pose` or `animated_pose`, down the hierarchy
+    /// into model space transforms.
+    pub fn world_matrices(&self, pose: &[Matrix]) -> Vec<Matrix> {
+        let mut world: Vec<Matrix> = Vec::with_capacity(self.frames.len());
+
+        for (frame, local) in self.frames.iter().zip(pose) {
+            let matrix = match frame.parent {
+                Some(parent) if self.clump.has_hierarchy => local * &world[parent],
+                _ => local.clone(),
+            };
+
+            world.push(matrix);
+        }
+
+        world
+    }
+
+    /// The matrices that move each bone from the bind pose to `world`.
+    pub fn skinning_matrices(&self, world: &[Matrix]) -> Vec<Matrix> {
+        self.bones()
+            .iter()
+            .zip(&self.bone_frames)
+            .map(
+                |(bone, frame)| match frame.and_then(|frame| world.get(frame)) {
+                    Some(world) => &bone.inverted_base_pose * world,
+                    None => Matrix::identity(),
+                },
+            )
+            .collect()
+    }
+
+    /// Blends every vertex of `model_part` between its two bones, `weight` going to the first.
+    /// Vertices without bone indices are left in place.
+    pub fn skin(&self, model_part: &ModelPart, world: &[Matrix]) -> SkinnedVertices {
+        let skinning_matrices = self.skinning_matrices(world);
+        let identity = Matrix::identity();
+        let bone = |index: u16| skinning_matrices.get(index as usize).unwrap_or(&identity);
+        let has_normals = model_part
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal.is_some());
+        let mut positions = Vec::with_capacity(model_part.vertices.len());
+        let mut normals = Vec::new();
+
+        for vertex in &model_part.vertices {
+            let position = vertex.vertex.unwrap_or_default();
+            let normal = vertex.normal.unwrap_or_default();
+
+            let (position, normal) = match vertex.indices {
+                Some((index0, index1)) => {
+                    let weight = vertex.weight.unwrap_or(1.0).clamp(0.0, 1.0);
+                    let (bone0, bone1) = (bone(index0), bone(index1));
+
+                    (
+                        bone0.transform_point(&position) * weight
+                            + bone1.transform_point(&position) * (1.0 - weight),
+                        (bone0.transform_vector(&normal) * weight
+                            + bone1.transform_vector(&normal) * (1.0 - weight))
+                            .normalized(),
+                    )
+                }
+                None => (position, normal),
+            };
+
+            positions.push(position);
+
+            if has_normals {
+                normals.push(normal);
+            }
+        }
+
+        SkinnedVertices { positions, normals }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameChild, Vector4, Vertex};
+
+    fn frame(bone_index: i32, x: f32) -> Chunk {
+        let mut matrix = Matrix::identity();
+
+        matrix.position = Vector4::new(x, 0.0, 0.0, 1.0);
+
+        Chunk::BoneObj(Frame {
+            local_transform_matrix: matrix.clone(),
+            global_transform_matrix: matrix,
+            bone_index,
+            flags: 0,
+            id: bone_index as u32,
+            name: String::new(),
+        })
+    }
+
+    #[test]
+    fn skins_vertices_with_moved_bone() {
+        let bone = |x: f32| {
+            let mut inverted_base_pose = Matrix::identity();
+
+            inverted_base_pose.position = Vector4::new(-x, 0.0, 0.0, 1.0);
+
+            Bone {
+                bone_id: 0,
+                inverted_base_pose,
+            }
+        };
+        let model = Bsp {
+            chunks: vec![
+                Chunk::LevelObj(FrameChild { stream_depth: 0 }),
+                frame(0, 1.0),
+                Chunk::LevelObj(FrameChild { stream_depth: 1 }),
+                frame(1, 1.0),
+                Chunk::SkinObj(Clump {
+                    base_flags: 0,
+                    name_hash: 0,
+                    flags: 0,
+                    floor_flags: 0,
+                    bone_count: vec![bone(1.0), bone(2.0)],
+                    has_hierarchy: true,
+                    default_animation_hash: 0,
+                    mirror_data: None,
+                }),
+            ],
+        };
+        let skeleton = Skeleton::new(&model).unwrap();
+        let mut pose = skeleton.bind_pose();
+
+        assert_eq!(skeleton.frames[1].parent, Some(0));
+
+        pose[0].position = Vector4::new(3.0, 0.0, 0.0, 1.0);
+
+        let world = skeleton.world_matrices(&pose);
+        let model_part = ModelPart {
+            vertices: vec![Vertex {
+                vertex: Some(Vector3::new(2.0, 1.0, 0.0)),
+                weight: Some(0.5),
+                indices: Some((0, 1)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            skeleton.skin(&model_part, &world).positions,
+            vec![Vector3::new(4.0, 1.0, 0.0)]
+        );
+    }
+}
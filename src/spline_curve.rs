@@ -0,0 +1,338 @@
+use crate::{Spline, SplineType, Vector3};
+
+/// How finely each segment is sampled to measure arc length and to test areas.
+pub const SPLINE_SAMPLES_PER_SEGMENT: usize = 16;
+
+impl Spline {
+    /// The number of segments, or `None` if the spline cannot be evaluated: its type is unknown,
+    /// or it is a Bézier spline whose points do not make whole segments, which takes `3n + 1`
+    /// points when open and `3n` when closed.
+    pub fn segment_count(&self) -> Option<usize> {
+        let count = self.points.len();
+
+        match (self.type_, self.closed) {
+            (SplineType::Unknown(_), _) => None,
+            (SplineType::Bezier, false) if count == 0 => Some(0),
+            (SplineType::Bezier, false) => (count - 1).is_multiple_of(3).then_some((count - 1) / 3),
+            (SplineType::Bezier, true) => count.is_multiple_of(3).then_some(count / 3),
+            (_, false) => Some(count.saturating_sub(1)),
+            (_, true) if count > 1 => Some(count),
+            _ => Some(0),
+        }
+    }
+
+    /// The point at `t`, which runs from 0 at the start to 1 at the end with every segment
+    /// taking an equal share. Returns `None` if the spline has no points or cannot be evaluated.
+    pub fn position(&self, t: f32) -> Option<Vector3> {
+        if self.segment_count()? == 0 {
+            return self.points.first().copied();
+        }
+
+        let (segment, u) = self.locate(t);
+        let [p0, p1, p2, p3] = self.segment_points(segment);
+
+        Some(match self.type_ {
+            SplineType::CatmullRom => {
+                (p1 * 2.0
+                    + (p2 - p0) * u
+                    + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * (u * u)
+                    + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * (u * u * u))
+                    * 0.5
+            }
+            SplineType::Bezier => {
+                let v = 1.0 - u;
+
+                p0 * (v * v * v)
+                    + p1 * (3.0 * v * v * u)
+                    + p2 * (3.0 * v * u * u)
+                    + p3 * (u * u * u)
+            }
+            _ => p1.lerp(&p2, u),
+        })
+    }
+
+    /// The unit direction of the spline at `t`, or `None` where it is undefined.
+    pub fn tangent(&self, t: f32) -> Option<Vector3> {
+        if self.segment_count()? == 0 {
+            return None;
+        }
+
+        let (segment, u) = self.locate(t);
+        let [p0, p1, p2, p3] = self.segment_points(segment);
+        let derivative = match self.type_ {
+            SplineType::CatmullRom => {
+                ((p2 - p0)
+                    + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * (2.0 * u)
+                    + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * (3.0 * u * u))
+                    * 0.5
+            }
+            SplineType::Bezier => {
+                let v = 1.0 - u;
+
+                (p1 - p0) * (3.0 * v * v) + (p2 - p1) * (6.0 * v * u) + (p3 - p2) * (3.0 * u * u)
+            }
+            _ => p2 - p1,
+        };
+
+        (derivative.length() > 0.0).then(|| derivative.normalized())
+    }
+
+    /// Samples every segment `samples_per_segment` times, including both ends of the spline.
+    /// Closed splines end on their first point. Returns `None` if the spline cannot be
+    /// evaluated.
+    pub fn sample(&self, samples_per_segment: usize) -> Option<Vec<Vector3>> {
+        let sample_count = self.segment_count()? * samples_per_segment.max(1);
+
+        if sample_count == 0 {
+            return Some(self.points.first().copied().into_iter().collect());
+        }
+
+        Some(
+            (0..=sample_count)
+                .filter_map(|sample| self.position(sample as f32 / sample_count as f32))
+                .collect(),
+        )
+    }
+
+    /// The approximate length of the spline, or `None` if it cannot be evaluated.
+    pub fn length(&self) -> Option<f32> {
+        Some(
+            self.arc_lengths()?
+                .last()
+                .map_or(0.0, |(_, length)| *length),
+        )
+    }
+
+    /// The parameter `t` at which the spline is `distance` long, clamped to the spline.
+    pub fn parameter_at_distance(&self, distance: f32) -> Option<f32> {
+        let arc_lengths = self.arc_lengths()?;
+        let index = arc_lengths.partition_point(|(_, length)| *length < distance);
+
+        Some(match (index.checked_sub(1), arc_lengths.get(index)) {
+            (Some(previous), Some((t1, length1))) => {
+                let (t0, length0) = arc_lengths[previous];
+                let span = length1 - length0;
+
+                if span > 0.0 {
+                    t0 + (t1 - t0) * (distance - length0) / span
+                } else {
+                    *t1
+                }
+            }
+            (None, _) => 0.0,
+            (_, None) => 1.0,
+        })
+    }
+
+    /// The point `distance` along the spline, so that equal steps move at constant speed.
+    pub fn position_at_distance(&self, distance: f32) -> Option<Vector3> {
+        self.position(self.parameter_at_distance(distance)?)
+    }
+
+    /// Whether `point`, projected onto the XZ plane, lies inside the area the spline encloses,
+    /// or `None` if the spline cannot be evaluated. Open splines enclose nothing.
+    pub fn area_contains(&self, point: &Vector3) -> Option<bool> {
+        let outline = match self.type_ {
+            SplineType::Polyline => self.points.clone(),
+            _ => self.sample(SPLINE_SAMPLES_PER_SEGMENT)?,
+        };
+
+        if !self.closed || self.points.len() < 3 {
+            return Some(false);
+        }
+
+        // Counts the edges a ray along +x crosses.
+        Some(
+            outline
+                .iter()
+                .zip(outline.iter().cycle().skip(1))
+                .filter(|(a, b)| {
+                    (a.z > point.z) != (b.z > point.z)
+                        && point.x < a.x + (point.z - a.z) / (b.z - a.z) * (b.x - a.x)
+                })
+                .count()
+                % 2
+                == 1,
+        )
+    }
+
+    /// Maps `t` to a segment and the position within it, for splines with segments.
+    fn locate(&self, t: f32) -> (usize, f32) {
+        let segment_count = self.segment_count().unwrap_or(0).max(1);
+        let scaled = t.clamp(0.0, 1.0) * segment_count as f32;
+        let segment = (scaled as usize).min(segment_count - 1);
+
+        (segment, scaled - segment as f32)
+    }
+
+    /// The four points a segment is evaluated from. Polyline and Catmull-Rom segments run
+    /// between the middle two.
+    fn segment_points(&self, segment: usize) -> [Vector3; 4] {
+        let count = self.points.len() as isize;
+        let point = |index: isize| {
+            let index = if self.closed {
+                index.rem_euclid(count)
+            } else {
+                index.clamp(0, count - 1)
+            };
+
+            self.points[index as usize]
+        };
+        let first = match self.type_ {
+            SplineType::Bezier => segment as isize * 3,
+            _ => segment as isize - 1,
+        };
+
+        [
+            point(first),
+            point(first + 1),
+            point(first + 2),
+            point(first + 3),
+        ]
+    }
+
+    /// The parameter and the length of the spline up to it at every sample.
+    fn arc_lengths(&self) -> Option<Vec<(f32, f32)>> {
+        let sample_count = self.segment_count()? * SPLINE_SAMPLES_PER_SEGMENT;
+        let mut arc_lengths = Vec::with_capacity(sample_count + 1);
+        let mut previous = None;
+        let mut length = 0.0;
+
+        for sample in 0..=sample_count {
+            let t = sample as f32 / sample_count.max(1) as f32;
+            let Some(position) = self.position(t) else {
+                break;
+            };
+
+            if let Some(previous) = previous {
+                length += (position - previous).length();
+            }
+
+            arc_lengths.push((t, length));
+            previous = Some(position);
+        }
+
+        Some(arc_lengths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spline(type_: SplineType, closed: bool, points: &[(f32, f32)]) -> Spline {
+        Spline {
+            points: points
+                .iter()
+                .map(|(x, z)| Vector3::new(*x, 0.0, *z))
+                .collect(),
+            closed,
+            type_,
+        }
+    }
+
+    #[test]
+    fn evaluates_curve_types() {
+        let polyline = spline(
+            SplineType::Polyline,
+            false,
+            &[(0.0, 0.0), (1.0, 0.0), (1.0, 3.0)],
+        );
+
+        assert_eq!(polyline.position(0.25), Some(Vector3::new(0.5, 0.0, 0.0)));
+        assert_eq!(polyline.tangent(0.75), Some(Vector3::new(0.0, 0.0, 1.0)));
+        assert!((polyline.length().unwrap() - 4.0).abs() < 1e-4);
+        assert!(
+            (polyline.position_at_distance(2.0).unwrap() - Vector3::new(1.0, 0.0, 1.0)).length()
+                < 1e-4
+        );
+
+        let catmull_rom = spline(
+            SplineType::CatmullRom,
+            false,
+            &[(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)],
+        );
+
+        assert_eq!(catmull_rom.position(0.5), Some(Vector3::new(1.0, 0.0, 1.0)));
+        assert_eq!(catmull_rom.sample(4).unwrap().len(), 9);
+
+        let bezier = spline(
+            SplineType::Bezier,
+            false,
+            &[(0.0, 0.0), (0.0, 1.0), (2.0, 1.0), (2.0, 0.0)],
+        );
+
+        assert_eq!(bezier.position(0.5), Some(Vector3::new(1.0, 0.0, 0.75)));
+        assert_eq!(bezier.tangent(0.0), Some(Vector3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn rejects_unevaluable_splines() {
+        let points = [(0.0, 0.0), (0.0, 1.0), (2.0, 1.0), (2.0, 0.0), (3.0, 0.0)];
+
+        assert_eq!(
+            spline(SplineType::Bezier, false, &points).segment_count(),
+            None
+        );
+        assert_eq!(
+            spline(SplineType::Bezier, false, &points).position(0.5),
+            None
+        );
+        assert_eq!(
+            spline(SplineType::Unknown(7), false, &points).length(),
+            None
+        );
+        assert_eq!(
+            spline(SplineType::Unknown(7), true, &points).area_contains(&Vector3::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn tests_closed_areas() {
+        let square = spline(
+            SplineType::CatmullRom,
+            true,
+            &[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)],
+        );
+
+        assert_eq!(
+            square.area_contains(&Vector3::new(1.0, 5.0, 1.0)),
+            Some(true)
+        );
+        assert_eq!(
+            square.area_contains(&Vector3::new(3.0, 0.0, 1.0)),
+            Some(false)
+        );
+        assert_eq!(
+            Spline {
+                closed: false,
+                ..square
+            }
+            .area_contains(&Vector3::new(1.0, 0.0, 1.0)),
+            Some(false)
+        );
+    }
+
+    mod levels {
+        use crate::{bsp::decode_level, Chunk, SplineType};
+        use test_case::test_case;
+
+        /// Checks the assumed type numbering against the areas of real levels: every type is
+        /// known and every Bézier area has whole segments.
+        #[test_case("armybase" ; "armybase")]
+        #[test_case("Asylum" ; "asylum")]
+        #[test_case("ghostbreakers" ; "ghostbreakers")]
+        #[test_case("summoners" ; "summoners")]
+        fn matches_assumed_numbering(asset: &str) {
+            for chunk in &decode_level(asset).chunks {
+                let Chunk::Area(area) = chunk else {
+                    continue;
+                };
+
+                assert!(!matches!(area.type_, SplineType::Unknown(_)), "{area:?}");
+                assert!(area.segment_count().is_some(), "{area:?}");
+            }
+        }
+    }
+}
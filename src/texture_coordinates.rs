@@ -0,0 +1,183 @@
+use crate::{EnvmapType, Material, Matrix, TextureGenerator, Vector3, Vertex};
+
+/// The coordinates a material stage samples its texture with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StageCoordinates {
+    Uv(f32, f32),
+    /// A direction to sample a cube map with, produced by `EnvmapType::Cubic` materials.
+    Cube(Vector3),
+}
+
+impl Material {
+    /// Computes the texture coordinates of `stage` for a vertex, following Direct3D's fixed
+    /// function pipeline: the coordinates are either taken from the stage's `uv_set` or generated
+    /// in camera space, then transformed by the stage matrix.
+    ///
+    /// `model_view` transforms the vertex from model to camera space. Returns `None` when the
+    /// stage has no texture or the vertex lacks the data the generator needs.
+    pub fn stage_coordinates(
+        &self,
+        stage: usize,
+        vertex: &Vertex,
+        model_view: &Matrix,
+    ) -> Option<StageCoordinates> {
+        let texture = self.textures.get(stage)?;
+
+        if texture.name.is_empty() {
+            return None;
+        }
+
+        let identity = Matrix::identity();
+        let matrix = self.matrices[stage].as_ref().unwrap_or(&identity);
+        let camera_position = || {
+            vertex
+                .vertex
+                .as_ref()
+                .map(|position| model_view.transform_point(position))
+        };
+        let camera_normal = || {
+            vertex
+                .normal
+                .as_ref()
+                .map(|normal| model_view.transform_vector(normal).normalized())
+        };
+
+        let generated = match self.generators[stage] {
+            TextureGenerator::PassThrough => {
+                let (u, v) = *vertex.uvs.get(texture.uv_set as usize)?;
+
+                // Two dimensional coordinates are extended with 1, so the third row translates.
+                let transformed = matrix.transform_vector(&Vector3::new(u, v, 1.0));
+
+                return Some(StageCoordinates::Uv(transformed.x, transformed.y));
+            }
+            TextureGenerator::CameraSpaceNormal => camera_normal()?,
+            TextureGenerator::CameraSpacePosition => camera_position()?,
+            TextureGenerator::CameraSpaceReflectionVector | TextureGenerator::SphereMap => {
+                reflect(&camera_position()?, &camera_normal()?)
+            }
+            TextureGenerator::Unknown(_) => return None,
+        };
+
+        if self.envmap_type == EnvmapType::Cubic {
+            return Some(StageCoordinates::Cube(
+                matrix.transform_vector(&generated).normalized(),
+            ));
+        }
+
+        let generated = match (self.generators[stage], self.envmap_type) {
+            (TextureGenerator::SphereMap, _)
+            | (TextureGenerator::CameraSpaceReflectionVector, EnvmapType::Spherical) => {
+                sphere_map(&generated)
+            }
+            (TextureGenerator::CameraSpaceReflectionVector, EnvmapType::Planar) => planar_map(
+                &camera_position()?,
+                &generated,
+                self.planar_sheer_envmap_distance,
+            ),
+            _ => generated,
+        };
+
+        let transformed = matrix.transform_point(&generated);
+
+        Some(StageCoordinates::Uv(transformed.x, transformed.y))
+    }
+}
+
+/// Reflects the direction from the camera to `position` about `normal`.
+fn reflect(position: &Vector3, normal: &Vector3) -> Vector3 {
+    let eye = position.normalized();
+
+    eye - *normal * (2.0 * normal.dot(&eye))
+}
+
+/// Maps a reflection vector into the unit square like `D3DTSS_TCI_SPHEREMAP`.
+fn sphere_map(reflection: &Vector3) -> Vector3 {
+    let m = 2.0
+        * (reflection.x * reflection.x
+            + reflection.y * reflection.y
+            + (reflection.z - 1.0) * (reflection.z - 1.0))
+            .sqrt();
+
+    if m == 0.0 {
+        return Vector3::new(0.5, 0.5, 0.0);
+    }
+
+    Vector3::new(reflection.x / m + 0.5, -reflection.y / m + 0.5, 0.0)
+}
+
+/// Projects the reflection onto a plane `distance` units away from the vertex, shearing the
+/// environment with the vertex position. Coordinates span one unit per `distance`.
+fn planar_map(position: &Vector3, reflection: &Vector3, distance: f32) -> Vector3 {
+    if distance <= 0.0 {
+        return sphere_map(reflection);
+    }
+
+    let hit = *position + *reflection * distance;
+
+    Vector3::new(
+        hit.x / distance * 0.5 + 0.5,
+        -hit.y / distance * 0.5 + 0.5,
+        0.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MaterialTexture, Vector4};
+
+    fn material(generator: TextureGenerator, matrix: Option<Matrix>) -> Material {
+        let mut textures: [MaterialTexture; 5] = Default::default();
+
+        textures[0].name = "stage".into();
+        textures[0].uv_set = 1;
+
+        Material {
+            textures,
+            matrices: [matrix, None, None, None, None],
+            generators: [generator; 5],
+            ..Default::default()
+        }
+    }
+
+    fn vertex() -> Vertex {
+        Vertex {
+            vertex: Some(Vector3::new(0.0, 0.0, 5.0)),
+            normal: Some(Vector3::new(0.0, 0.0, -1.0)),
+            reciprocal_homogeneous_w: None,
+            diffuse: None,
+            weight: None,
+            indices: None,
+            uvs: vec![(0.0, 0.0), (0.25, 0.5)],
+        }
+    }
+
+    #[test]
+    fn transforms_selected_uv_set() {
+        let mut scroll = Matrix::identity();
+
+        scroll.at = Vector4::new(0.5, 0.0, 1.0, 0.0);
+
+        let material = material(TextureGenerator::PassThrough, Some(scroll));
+
+        assert_eq!(
+            material.stage_coordinates(0, &vertex(), &Matrix::identity()),
+            Some(StageCoordinates::Uv(0.75, 0.5))
+        );
+        assert_eq!(
+            material.stage_coordinates(1, &vertex(), &Matrix::identity()),
+            None
+        );
+    }
+
+    #[test]
+    fn sphere_maps_facing_reflection_to_center() {
+        let material = material(TextureGenerator::SphereMap, None);
+
+        assert_eq!(
+            material.stage_coordinates(0, &vertex(), &Matrix::identity()),
+            Some(StageCoordinates::Uv(0.5, 0.5))
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{NavigationMesh, Waypoint};
+
+/// The waypoints of a navigation mesh and the links between them.
+#[derive(Clone, Debug)]
+pub struct WaypointGraph<'a> {
+    pub navigation_mesh: &'a NavigationMesh,
+    /// The waypoints linked to or from each waypoint, ignoring the direction of the links.
+    pub undirected: Vec<Vec<usize>>,
+}
+
+impl<'a> WaypointGraph<'a> {
+    /// Builds the graph, dropping links to waypoints that do not exist.
+    pub fn new(navigation_mesh: &'a NavigationMesh) -> Self {
+        let waypoint_count = navigation_mesh.waypoints.len();
+        let mut undirected = vec![Vec::new(); waypoint_count];
+
+        for (source, links) in navigation_mesh
+            .links
+            .iter()
+            .enumerate()
+            .take(waypoint_count)
+        {
+            for link in links {
+                let target = link.waypoint_index as usize;
+
+                if target >= waypoint_count || target == source {
+                    continue;
+                }
+
+                for (from, to) in [(source, target), (target, source)] {
+                    if !undirected[from].contains(&to) {
+                        undirected[from].push(to);
+                    }
+                }
+            }
+        }
+
+        Self {
+            navigation_mesh,
+            undirected,
+        }
+    }
+
+    pub fn waypoint(&self, waypoint: usize) -> Option<&'a Waypoint> {
+        self.navigation_mesh.waypoints.get(waypoint)
+    }
+
+    /// The links leaving `waypoint`, with their flags.
+    pub fn neighbours(&self, waypoint: usize) -> impl Iterator<Item = (usize, u32)> + 'a {
+        let waypoint_count = self.navigation_mesh.waypoints.len();
+
+        self.navigation_mesh
+            .links
+            .get(waypoint)
+            .into_iter()
+            .flatten()
+            .map(|link| (link.waypoint_index as usize, link.flags))
+            .filter(move |(target, _)| *target < waypoint_count)
+    }
+
+    /// The flags of the link from `from` to `to`, if there is one.
+    pub fn edge_flags(&self, from: usize, to: usize) -> Option<u32> {
+        self.navigation_mesh
+            .links
+            .get(from)?
+            .iter()
+            .find(|link| link.waypoint_index as usize == to)
+            .map(|link| link.flags)
+    }
+
+    /// Groups the waypoints that can reach each other when links are followed both ways. Each
+    /// component is sorted and the components are ordered by their first waypoint.
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        let mut component_of = vec![None; self.undirected.len()];
+        let mut components = Vec::new();
+
+        for start in 0..self.undirected.len() {
+            if component_of[start].is_some() {
+                continue;
+            }
+
+            let mut component = Vec::new();
+            let mut stack = vec![start];
+
+            component_of[start] = Some(components.len());
+
+            while let Some(waypoint) = stack.pop() {
+                component.push(waypoint);
+
+                for neighbour in &self.undirected[waypoint] {
+                    if component_of[*neighbour].is_none() {
+                        component_of[*neighbour] = Some(components.len());
+                        stack.push(*neighbour);
+                    }
+                }
+            }
+
+            component.sort_unstable();
+            components.push(component);
+        }
+
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, Vector3};
+
+    #[test]
+    fn finds_neighbours_and_components() {
+        let link = |waypoint_index, flags| Link {
+            waypoint_index,
+            flags,
+        };
+        let navigation_mesh = NavigationMesh {
+            waypoints: (0..4)
+                .map(|_| Waypoint {
+                    position: Vector3::default(),
+                    flags: 0,
+                })
+                .collect(),
+            links: vec![
+                vec![link(1, 3)],
+                Vec::new(),
+                vec![link(3, 0), link(9, 0)],
+                Vec::new(),
+            ],
+        };
+        let graph = WaypointGraph::new(&navigation_mesh);
+
+        assert_eq!(graph.neighbours(0).collect::<Vec<_>>(), vec![(1, 3)]);
+        assert_eq!(graph.neighbours(2).collect::<Vec<_>>(), vec![(3, 0)]);
+        assert_eq!(graph.edge_flags(0, 1), Some(3));
+        assert_eq!(graph.edge_flags(1, 0), None);
+        assert_eq!(graph.connected_components(), vec![vec![0, 1], vec![2, 3]]);
+    }
+}
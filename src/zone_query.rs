@@ -0,0 +1,226 @@
+//! Zone lookup and adjacency.
+//!
+//! A zone's `spline_index` is assumed to index the `Area` chunks of the level; out of range
+//! indices, and areas that cannot be evaluated, leave the zone bounded by its box alone. The
+//! zones of a sector octree leaf are found with `SectorOctreeLeaf::zones`.
+
+use crate::{SectorOctree, SectorOctreeOctant, Spline, Vector3, Zones};
+use std::collections::{BTreeSet, VecDeque};
+
+/// Which zones connect, judged from the sector octree.
+///
+/// Zones listed by the same leaf are linked, and so are zones of leaves whose octants touch when
+/// their own boxes touch as well. Portals are not consulted, so zones separated by a wall within
+/// touching distance show as connected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ZoneGraph {
+    /// The neighbours of each zone, by zone index.
+    pub adjacency: Vec<BTreeSet<usize>>,
+}
+
+impl Zones {
+    /// The zones containing `point` on any of the floors in `floor_flags`. Zones without floor
+    /// flags are on every floor.
+    pub fn zones_at(&self, point: &Vector3, floor_flags: u32, areas: &[&Spline]) -> Vec<usize> {
+        self.zones
+            .iter()
+            .enumerate()
+            .filter(|(_, zone)| zone.floor_flags == 0 || zone.floor_flags & floor_flags != 0)
+            .filter(|(_, zone)| zone.bounding_box.contains(point))
+            .filter(|(_, zone)| {
+                areas
+                    .get(zone.spline_index as usize)
+                    .and_then(|area| area.area_contains(point))
+                    .unwrap_or(true)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Connects the zones that share a leaf of `octree` or sit on either side of a leaf boundary.
+    /// See `ZoneGraph` for how far this can be trusted.
+    pub fn graph(&self, octree: &SectorOctree) -> ZoneGraph {
+        let mut adjacency = vec![BTreeSet::new(); self.zones.len()];
+        let mut link = |a: usize, b: usize| {
+            if a != b {
+                adjacency[a].insert(b);
+                adjacency[b].insert(a);
+            }
+        };
+
+        for leaf in &octree.leaves {
+            let zones = leaf.zones(self).collect::<Vec<_>>();
+
+            for a in &zones {
+                for b in &zones {
+                    link(*a, *b);
+                }
+            }
+        }
+
+        let leaves = octree
+            .octants
+            .iter()
+            .filter_map(|octant| match octant {
+                SectorOctreeOctant::Leaf {
+                    bounds, leaf_index, ..
+                } => octree
+                    .leaves
+                    .get(*leaf_index as usize)
+                    .map(|leaf| (bounds, leaf.zones(self).collect::<Vec<_>>())),
+                SectorOctreeOctant::Subtree { .. } => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (index, (bounds, zones)) in leaves.iter().enumerate() {
+            for (other_bounds, other_zones) in &leaves[index + 1..] {
+                if !bounds.intersects(other_bounds) {
+                    continue;
+                }
+
+                for a in zones {
+                    for b in other_zones {
+                        if self.zones[*a]
+                            .bounding_box
+                            .intersects(&self.zones[*b].bounding_box)
+                        {
+                            link(*a, *b);
+                        }
+                    }
+                }
+            }
+        }
+
+        ZoneGraph { adjacency }
+    }
+}
+
+impl ZoneGraph {
+    pub fn neighbours(&self, zone: usize) -> impl Iterator<Item = usize> + '_ {
+        self.adjacency.get(zone).into_iter().flatten().copied()
+    }
+
+    /// The zones reachable from `zone`, itself included, in breadth first order.
+    pub fn reachable(&self, zone: usize) -> Vec<usize> {
+        let mut visited = vec![false; self.adjacency.len()];
+        let mut reachable = Vec::new();
+        let mut queue = VecDeque::from([zone]);
+
+        while let Some(zone) = queue.pop_front() {
+            if zone >= visited.len() || std::mem::replace(&mut visited[zone], true) {
+                continue;
+            }
+
+            reachable.push(zone);
+            queue.extend(self.neighbours(zone));
+        }
+
+        reachable
+    }
+
+    pub fn are_connected(&self, a: usize, b: usize) -> bool {
+        self.reachable(a).contains(&b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoundingBox, SectorOctreeLeaf, SectorOctreeOctant, SplineType, Zone};
+
+    fn zone(x: f32, floor_flags: u32, spline_index: u32) -> Zone {
+        Zone {
+            bounding_box: BoundingBox {
+                infimum: Vector3::new(x, 0.0, 0.0),
+                supremum: Vector3::new(x + 2.0, 2.0, 2.0),
+            },
+            hash: 0,
+            ngon_index: 0,
+            spline_index,
+            clump_index: 0,
+            floor_flags,
+            zone_top: None,
+        }
+    }
+
+    fn leaf(zone: u32, zone_count: u32) -> SectorOctreeLeaf {
+        SectorOctreeLeaf {
+            sector_floor_flag: 0,
+            world_blocks_count: 0,
+            world_block_index: None,
+            zone_count,
+            zone,
+        }
+    }
+
+    #[test]
+    fn finds_zones_and_connections() {
+        let zones = Zones {
+            octant_connections: vec![0, 1, 2, 3],
+            zones: vec![
+                zone(0.0, 0, u32::MAX),
+                zone(1.0, 2, 0),
+                zone(4.0, 0, u32::MAX),
+                zone(8.0, 0, u32::MAX),
+            ],
+        };
+        let area = Spline {
+            points: vec![
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.5, 0.0, 0.0),
+                Vector3::new(1.5, 0.0, 2.0),
+                Vector3::new(1.0, 0.0, 2.0),
+            ],
+            closed: true,
+            type_: SplineType::Polyline,
+        };
+        let point = |x| Vector3::new(x, 1.0, 1.0);
+
+        assert_eq!(zones.zones_at(&point(1.2), 2, &[&area]), vec![0, 1]);
+        assert_eq!(zones.zones_at(&point(1.2), 1, &[&area]), vec![0]);
+        assert_eq!(zones.zones_at(&point(1.8), 2, &[&area]), vec![0]);
+
+        let octree = SectorOctree {
+            blocks: Vec::new(),
+            leaves: vec![leaf(0, 2), leaf(1, 2), leaf(3, 1)],
+            octants: Vec::new(),
+        };
+        let graph = zones.graph(&octree);
+
+        assert_eq!(graph.neighbours(1).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(graph.reachable(0), vec![0, 1, 2]);
+        assert!(!graph.are_connected(0, 3));
+    }
+
+    #[test]
+    fn connects_touching_zones_of_touching_leaves() {
+        let zones = Zones {
+            octant_connections: vec![0, 1, 2, 3],
+            zones: vec![
+                zone(0.0, 0, u32::MAX),
+                zone(4.0, 0, u32::MAX),
+                zone(6.0, 0, u32::MAX),
+                zone(9.0, 0, u32::MAX),
+            ],
+        };
+        let octant = |x: f32, leaf_index| SectorOctreeOctant::Leaf {
+            bounds: BoundingBox {
+                infimum: Vector3::new(x, 0.0, 0.0),
+                supremum: Vector3::new(x + 6.0, 6.0, 6.0),
+            },
+            flags: 0,
+            leaf_index,
+        };
+        // The zones 1 and 2 meet on the boundary between the two leaves.
+        let octree = SectorOctree {
+            blocks: Vec::new(),
+            leaves: vec![leaf(0, 2), leaf(2, 2)],
+            octants: vec![octant(0.0, 0), octant(6.0, 1)],
+        };
+        let graph = zones.graph(&octree);
+
+        assert_eq!(graph.neighbours(1).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(graph.neighbours(3).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(graph.reachable(0), vec![0, 1, 2, 3]);
+    }
+}